@@ -1,30 +1,91 @@
-// Intentionally does not implement Copy, as that would permit duplication glitches.
-#[repr(u8)]
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub enum BlockName {
-    Air = 0,
-    Stone = 1,
-    Grass = 2,
+mod registry;
+
+pub use registry::{registry, BlockInfo, Fluid};
+
+/// Which kind of block something is.
+/// Everything else about the kind of block, such as its color, is in the [`registry`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct BlockId(pub u8);
+
+// The blocks that the game refers to by name. `blocks.ron` must define each of them, with the same ID.
+impl BlockId {
+    pub const AIR: Self = BlockId(0);
+    pub const STONE: Self = BlockId(1);
+    pub const GRASS: Self = BlockId(2);
+    /// Fills everything beyond the walls of a finite world.
+    pub const BARRIER: Self = BlockId(3);
+    pub const DIRT: Self = BlockId(4);
+    pub const SAND: Self = BlockId(5);
+    pub const SNOW: Self = BlockId(6);
+    pub const WOOD: Self = BlockId(7);
+    pub const LEAVES: Self = BlockId(8);
+    pub const PLANKS: Self = BlockId(9);
+    pub const COAL: Self = BlockId(10);
+    pub const IRON: Self = BlockId(11);
+    pub const GOLD: Self = BlockId(12);
+    pub const DIAMOND: Self = BlockId(13);
+    pub const WATER: Self = BlockId(14);
+    pub const GRAVEL: Self = BlockId(15);
+
+    const BUILTIN: &'static [(Self, &'static str)] = &[
+        (Self::AIR, "air"),
+        (Self::STONE, "stone"),
+        (Self::GRASS, "grass"),
+        (Self::BARRIER, "barrier"),
+        (Self::DIRT, "dirt"),
+        (Self::SAND, "sand"),
+        (Self::SNOW, "snow"),
+        (Self::WOOD, "wood"),
+        (Self::LEAVES, "leaves"),
+        (Self::PLANKS, "planks"),
+        (Self::COAL, "coal"),
+        (Self::IRON, "iron"),
+        (Self::GOLD, "gold"),
+        (Self::DIAMOND, "diamond"),
+        (Self::WATER, "water"),
+        (Self::GRAVEL, "gravel"),
+    ];
+
+    pub fn info(self) -> &'static BlockInfo {
+        &registry()[self]
+    }
 }
 
+/// Extra data stored with a block, such as its orientation or fluid level.
+/// What it means depends on the block; most blocks leave it at zero.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct BlockState(pub u8);
+
+// Intentionally does not implement Copy, as that would permit duplication glitches.
 #[must_use = "Every time you drop this, an actual in-game block gets destroyed. If this is what you want, drop this explicitly."]
-pub struct Block(BlockName);
+pub struct Block {
+    id: BlockId,
+    state: BlockState,
+}
 
 impl std::ops::Deref for Block {
-    type Target = BlockName;
-    fn deref(&self) -> &BlockName {
-        &self.0
+    type Target = BlockId;
+    fn deref(&self) -> &BlockId {
+        &self.id
     }
 }
 
-impl PartialEq<BlockName> for Block {
-    fn eq(&self, other: &BlockName) -> bool {
-        self as &BlockName == other
+impl PartialEq<BlockId> for Block {
+    fn eq(&self, other: &BlockId) -> bool {
+        self as &BlockId == other
     }
 }
 
 impl Block {
-    pub const fn create(block: BlockName) -> Self {
-        Self(block)
+    pub const fn create(block: BlockId) -> Self {
+        Self::with_state(block, BlockState(0))
+    }
+
+    pub const fn with_state(block: BlockId, state: BlockState) -> Self {
+        Self { id: block, state }
+    }
+
+    pub fn state(&self) -> BlockState {
+        self.state
     }
 }
@@ -0,0 +1,160 @@
+use super::BlockId;
+
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Everything about a kind of block, other than where it is.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BlockInfo {
+    pub name: String,
+    pub id: u8,
+    /// RGBA, each from zero to one.
+    pub color: (f32, f32, f32, f32),
+    #[serde(default = "yes")]
+    pub solid: bool,
+    #[serde(default = "yes")]
+    pub opaque: bool,
+    /// How long the block takes to break. Negative if it can't be broken.
+    #[allow(dead_code)] // Blocks break instantly for now.
+    pub hardness: f32,
+    #[serde(default)]
+    pub light: u8,
+    #[serde(default)]
+    pub pattern: Pattern,
+    /// Whether the block flows, and how.
+    #[serde(default)]
+    pub fluid: Option<Fluid>,
+}
+
+#[derive(Debug, Copy, Clone, Deserialize)]
+pub struct Fluid {
+    /// How many blocks the fluid flows sideways from a source.
+    pub reach: u8,
+    /// How many ticks the fluid takes to flow one block.
+    pub delay: u64,
+}
+
+/// How the faces of a block are drawn on top of its color.
+/// The shader numbers these in the same order.
+#[derive(Debug, Copy, Clone, Default, Deserialize)]
+pub enum Pattern {
+    #[default]
+    Plain,
+    /// Dark lines along the edges of each face.
+    Grid,
+    /// Small squares of lighter and darker color.
+    Speckle,
+    /// Upright blades down the sides, and speckle on the top and bottom.
+    Blades,
+}
+
+/// Flowing levels are stored in a block's state, which leaves room for this many.
+const MAX_REACH: u8 = 127;
+
+fn yes() -> bool {
+    true
+}
+
+/// Every kind of block, by ID and by name.
+/// IDs that no block uses look up a bright magenta placeholder, so that mistakes stand out.
+pub struct Registry {
+    blocks: Vec<BlockInfo>,
+    by_name: HashMap<String, BlockId>,
+}
+
+impl Registry {
+    /// Read a list of blocks in RON, in the format of `blocks.ron`.
+    pub fn parse(ron: &str) -> Result<Self, String> {
+        let list: Vec<BlockInfo> = ron::from_str(ron).map_err(|e| e.to_string())?;
+
+        let mut blocks: Vec<BlockInfo> = (0..=255)
+            .map(|id| BlockInfo {
+                name: format!("unknown block {}", id),
+                id,
+                color: (1.0, 0.0, 1.0, 1.0),
+                solid: true,
+                opaque: true,
+                hardness: 0.0,
+                light: 0,
+                pattern: Pattern::Grid,
+                fluid: None,
+            })
+            .collect();
+        let mut by_name = HashMap::new();
+        let mut defined = [false; 256];
+        for info in list {
+            if defined[info.id as usize] {
+                return Err(format!("block ID {} is defined twice", info.id));
+            }
+            defined[info.id as usize] = true;
+            if info.opaque != (info.color.3 >= 1.0) {
+                return Err(format!(
+                    "block {:?} must have an alpha of 1 exactly if it is opaque",
+                    info.name
+                ));
+            }
+            if let Some(fluid) = info.fluid {
+                if info.solid || fluid.delay == 0 || !(1..=MAX_REACH).contains(&fluid.reach) {
+                    return Err(format!(
+                        "fluid {:?} must not be solid, must take at least a tick to flow, and must reach from 1 to {} blocks",
+                        info.name, MAX_REACH
+                    ));
+                }
+            }
+            if by_name
+                .insert(info.name.clone(), BlockId(info.id))
+                .is_some()
+            {
+                return Err(format!("block {:?} is defined twice", info.name));
+            }
+            let id = info.id as usize;
+            blocks[id] = info;
+        }
+
+        for &(id, name) in BlockId::BUILTIN {
+            if by_name.get(name) != Some(&id) {
+                return Err(format!("block {:?} must have ID {}", name, id.0));
+            }
+        }
+
+        Ok(Self { blocks, by_name })
+    }
+
+    /// Looks a block up by name, as written in world settings.
+    pub fn id(&self, name: &str) -> Option<BlockId> {
+        self.by_name.get(name).copied()
+    }
+
+    /// The color of every block ID in order, as RGBA bytes, ready to upload as a 256 by 1 texture.
+    pub fn color_table(&self) -> Vec<u8> {
+        self.blocks
+            .iter()
+            .flat_map(|info| {
+                let (r, g, b, a) = info.color;
+                vec![r, g, b, a]
+            })
+            .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+            .collect()
+    }
+
+    /// The pattern of every block ID in order, one byte each, ready to upload as a 256 by 1 texture.
+    pub fn pattern_table(&self) -> Vec<u8> {
+        self.blocks.iter().map(|info| info.pattern as u8).collect()
+    }
+}
+
+impl std::ops::Index<BlockId> for Registry {
+    type Output = BlockInfo;
+    fn index(&self, id: BlockId) -> &BlockInfo {
+        &self.blocks[id.0 as usize]
+    }
+}
+
+/// The blocks defined in `blocks.ron`, which is built into the game.
+pub fn registry() -> &'static Registry {
+    static REGISTRY: std::sync::OnceLock<Registry> = std::sync::OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        Registry::parse(include_str!("blocks.ron"))
+            .unwrap_or_else(|e| panic!("invalid blocks.ron: {}", e))
+    })
+}
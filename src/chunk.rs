@@ -1,84 +1,247 @@
-use crate::block::{Block, BlockName};
+use crate::block::{Block, BlockId, BlockState};
 
-// Changing this constant requires changing the fragment shader.
-pub const CHUNK_SIZE: usize = 8;
+/// A chunk keeps a palette of the blocks occurring in it, and a bit-packed palette index for each cell.
+/// Blocks with different states get different palette entries, so stateless chunks pay nothing for block states.
+/// A chunk consisting of a single kind of block stores no indices at all.
+pub struct Chunk {
+    // Width along each axis, from `Config::chunk_size`.
+    size: usize,
+    palette: Vec<PaletteEntry>,
+    // `None` means every cell refers to `palette[0]`.
+    indices: Option<PackedIndices>,
+}
 
-pub struct Chunk(Vec<Block>);
+struct PaletteEntry {
+    block: BlockId,
+    state: BlockState,
+    // Number of cells using this entry. Entries with a count of zero are free for reuse.
+    count: usize,
+}
 
 impl Chunk {
-    pub fn new(which_chunk: [isize; 4]) -> Self {
-        let mut chunk = Vec::with_capacity(CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE);
-        for w in 0..CHUNK_SIZE as isize {
-            for z in 0..CHUNK_SIZE as isize {
-                for y in 0..CHUNK_SIZE as isize {
-                    for x in 0..CHUNK_SIZE as isize {
-                        chunk.push(generate_block([
-                            which_chunk[0] * CHUNK_SIZE as isize + x,
-                            which_chunk[1] * CHUNK_SIZE as isize + y,
-                            which_chunk[2] * CHUNK_SIZE as isize + z,
-                            which_chunk[3] * CHUNK_SIZE as isize + w,
-                        ]))
+    /// A chunk made entirely of `block`.
+    pub fn filled(size: usize, block: BlockId) -> Self {
+        Self {
+            size,
+            palette: vec![PaletteEntry {
+                block,
+                state: BlockState::default(),
+                count: size.pow(4),
+            }],
+            indices: None,
+        }
+    }
+
+    /// Build a chunk from one block per cell, in the same order as the texture layout.
+    pub fn from_blocks(size: usize, blocks: &[BlockId]) -> Self {
+        debug_assert_eq!(blocks.len(), size.pow(4));
+
+        let mut palette: Vec<PaletteEntry> = Vec::new();
+        let mut raw_indices = Vec::with_capacity(blocks.len());
+        for &block in blocks {
+            let i = match palette.iter().position(|e| e.block == block) {
+                Some(i) => i,
+                None => {
+                    palette.push(PaletteEntry {
+                        block,
+                        state: BlockState::default(),
+                        count: 0,
+                    });
+                    palette.len() - 1
+                }
+            };
+            palette[i].count += 1;
+            raw_indices.push(i);
+        }
+
+        if palette.len() == 1 {
+            return Self {
+                size,
+                palette,
+                indices: None,
+            };
+        }
+
+        let mut indices = PackedIndices::new(PackedIndices::bits_for(palette.len()), blocks.len());
+        for (cell, &i) in raw_indices.iter().enumerate() {
+            indices.set(cell, i);
+        }
+        Self {
+            size,
+            palette,
+            indices: Some(indices),
+        }
+    }
+
+    fn volume(&self) -> usize {
+        self.size.pow(4)
+    }
+
+    pub fn pass_as_slice(&self, f: impl FnOnce(&[u8])) {
+        match &self.indices {
+            None => f(&vec![self.palette[0].block.0; self.volume()]),
+            Some(indices) => f(&(0..self.volume())
+                .map(|cell| self.palette[indices.get(cell)].block.0)
+                .collect::<Vec<u8>>()),
+        }
+    }
+
+    /// Call `f` on every cell `rel_pos` with `min[i] <= rel_pos[i] < max[i]`, in texture order.
+    pub fn for_each_in(
+        &self,
+        min: [usize; 4],
+        max: [usize; 4],
+        mut f: impl FnMut([usize; 4], BlockId),
+    ) {
+        for w in min[3]..max[3] {
+            for z in min[2]..max[2] {
+                for y in min[1]..max[1] {
+                    for x in min[0]..max[0] {
+                        let cell = x + self.size * (y + self.size * (z + self.size * w));
+                        f([x, y, z, w], self.palette[self.palette_index(cell)].block);
                     }
                 }
             }
         }
-        Self(chunk)
     }
 
-    pub fn pass_as_slice(&self, f: impl FnOnce(&[u8])) {
-        f(&self
-            .0
+    /// Put `block` into the cell at `rel_pos`, returning the block that was there before.
+    pub fn replace(&mut self, rel_pos: [isize; 4], block: Block) -> Block {
+        let cell = self.cell_index(rel_pos);
+        let old = self.palette_index(cell);
+        let (old_block, old_state) = (self.palette[old].block, self.palette[old].state);
+        if old_block == *block && old_state == block.state() {
+            return block;
+        }
+
+        let new = self.palette_index_of(*block, block.state());
+        let volume = self.volume();
+        self.indices
+            .get_or_insert_with(|| PackedIndices::new(1, volume))
+            .set(cell, new);
+        self.palette[old].count -= 1;
+        self.palette[new].count += 1;
+
+        if self.palette[new].count == volume {
+            // The chunk has become uniform, so the indices are no longer needed.
+            let entry = self.palette.swap_remove(new);
+            self.palette = vec![entry];
+            self.indices = None;
+        }
+
+        Block::with_state(old_block, old_state)
+    }
+
+    pub fn state(&self, rel_pos: [isize; 4]) -> BlockState {
+        self.palette[self.palette_index(self.cell_index(rel_pos))].state
+    }
+
+    /// Approximate number of bytes used by this chunk, including its heap allocations.
+    pub fn memory_usage(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.palette.capacity() * std::mem::size_of::<PaletteEntry>()
+            + self
+                .indices
+                .as_ref()
+                .map_or(0, |indices| indices.words.capacity() * 8)
+    }
+
+    #[rustfmt::skip]
+    fn cell_index(&self, [x, y, z, w]: [isize; 4]) -> usize {
+        x as usize + self.size * (
+        y as usize + self.size * (
+        z as usize + self.size *
+        w as usize))
+    }
+
+    fn palette_index(&self, cell: usize) -> usize {
+        match &self.indices {
+            None => 0,
+            Some(indices) => indices.get(cell),
+        }
+    }
+
+    // Find the palette entry for `block`, adding one if necessary.
+    fn palette_index_of(&mut self, block: BlockId, state: BlockState) -> usize {
+        if let Some(i) = self
+            .palette
             .iter()
-            .map(|b| *(b as &BlockName) as u8)
-            .collect::<Vec<u8>>())
+            .position(|e| e.block == block && e.state == state)
+        {
+            return i;
+        }
+        if let Some(i) = self.palette.iter().position(|e| e.count == 0) {
+            self.palette[i].block = block;
+            self.palette[i].state = state;
+            return i;
+        }
+
+        self.palette.push(PaletteEntry {
+            block,
+            state,
+            count: 0,
+        });
+        let bits = PackedIndices::bits_for(self.palette.len());
+        if let Some(indices) = &mut self.indices {
+            if bits > indices.bits {
+                *indices = indices.widened(bits, self.size.pow(4));
+            }
+        }
+        self.palette.len() - 1
     }
 }
 
 impl std::ops::Index<[isize; 4]> for Chunk {
-    type Output = Block;
-    fn index(&self, [x, y, z, w]: [isize; 4]) -> &Block {
-        #[rustfmt::skip]
-        let chunk_index =
-            x as usize + CHUNK_SIZE * (
-            y as usize + CHUNK_SIZE * (
-            z as usize + CHUNK_SIZE * (
-            w as usize)));
-        &self.0[chunk_index]
+    type Output = BlockId;
+    fn index(&self, rel_pos: [isize; 4]) -> &BlockId {
+        &self.palette[self.palette_index(self.cell_index(rel_pos))].block
     }
 }
 
-impl std::ops::IndexMut<[isize; 4]> for Chunk {
-    fn index_mut(&mut self, [x, y, z, w]: [isize; 4]) -> &mut Block {
-        #[rustfmt::skip]
-        let chunk_index =
-            x as usize + CHUNK_SIZE * (
-            y as usize + CHUNK_SIZE * (
-            z as usize + CHUNK_SIZE * (
-            w as usize)));
-        &mut self.0[chunk_index]
-    }
+/// One palette index per cell, packed into `u64`s.
+/// The width is a power of two, so that no index straddles two words.
+struct PackedIndices {
+    bits: usize,
+    words: Vec<u64>,
 }
 
-pub fn chunk_position(coords: [isize; 4]) -> ([isize; 4], [isize; 4]) {
-    let mut chunk = [0; 4];
-    let mut rel_pos = [0; 4];
-    for i in 0..4 {
-        chunk[i] = coords[i].div_euclid(CHUNK_SIZE as isize);
-        rel_pos[i] = coords[i].rem_euclid(CHUNK_SIZE as isize);
+impl PackedIndices {
+    fn new(bits: usize, len: usize) -> Self {
+        Self {
+            bits,
+            words: vec![0; (len * bits).div_ceil(64)],
+        }
+    }
+
+    fn bits_for(palette_len: usize) -> usize {
+        let mut bits = 1;
+        while 1 << bits < palette_len {
+            bits *= 2;
+        }
+        bits
     }
-    (chunk, rel_pos)
-}
 
-fn generate_block(block: [isize; 4]) -> Block {
-    match block {
-        [-1..=3, 0, 0, 0]
-        | [3, 0..=3, 0, 0]
-        | [3, 3, 0..=3, 0]
-        | [0..=3, 3, 3, 0]
-        | [0, 0..=3, 3, 0]
-        | [0, 0, 0..=3, 0] => Block::create(BlockName::Air),
-        [-1..=4, -1..=4, -1..=4, -1..=0] => Block::create(BlockName::Stone),
-        [-1..=4, -1..=4, -1..=4, 1] => Block::create(BlockName::Grass),
-        _ => Block::create(BlockName::Air),
+    fn mask(&self) -> u64 {
+        (1 << self.bits) - 1
+    }
+
+    fn get(&self, cell: usize) -> usize {
+        let bit = cell * self.bits;
+        ((self.words[bit / 64] >> (bit % 64)) & self.mask()) as usize
+    }
+
+    fn set(&mut self, cell: usize, value: usize) {
+        let bit = cell * self.bits;
+        let mask = self.mask();
+        let word = &mut self.words[bit / 64];
+        *word = (*word & !(mask << (bit % 64))) | ((value as u64 & mask) << (bit % 64));
+    }
+
+    fn widened(&self, bits: usize, len: usize) -> Self {
+        let mut out = Self::new(bits, len);
+        for cell in 0..len {
+            out.set(cell, self.get(cell));
+        }
+        out
     }
 }
@@ -0,0 +1,59 @@
+/// Sizes that the world, the renderer and the fragment shader must agree on.
+#[derive(Debug, Copy, Clone)]
+pub struct Config {
+    /// Width of a chunk along each axis, in blocks.
+    pub chunk_size: usize,
+    /// Width of the region uploaded to the GPU along each axis, in chunks.
+    /// The region is stored in a texture array with one layer per chunk, and WebGL only guarantees 256 layers.
+    pub render_chunks: usize,
+}
+
+impl Config {
+    pub fn new(chunk_size: usize, render_chunks: usize) -> Self {
+        // The shader finds chunks with shifts and masks.
+        assert!(
+            chunk_size.is_power_of_two(),
+            "chunk size must be a power of two"
+        );
+        assert!(
+            render_chunks.is_power_of_two(),
+            "render chunk count must be a power of two"
+        );
+        assert!(render_chunks.pow(4) <= 256, "too many render chunks");
+
+        Self {
+            chunk_size,
+            render_chunks,
+        }
+    }
+
+    pub fn chunk_volume(&self) -> usize {
+        self.chunk_size.pow(4)
+    }
+
+    /// log2 of the chunk size.
+    pub fn chunk_bits(&self) -> u32 {
+        self.chunk_size.trailing_zeros()
+    }
+
+    pub fn render_distance(&self) -> usize {
+        ((self.render_chunks - 1) * self.chunk_size) / 2
+    }
+
+    /// Split block coordinates into the chunk's coordinates and the position within the chunk.
+    pub fn chunk_position(&self, coords: [isize; 4]) -> ([isize; 4], [isize; 4]) {
+        let mut chunk = [0; 4];
+        let mut rel_pos = [0; 4];
+        for i in 0..4 {
+            chunk[i] = coords[i].div_euclid(self.chunk_size as isize);
+            rel_pos[i] = coords[i].rem_euclid(self.chunk_size as isize);
+        }
+        (chunk, rel_pos)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new(8, 4)
+    }
+}
@@ -4,7 +4,10 @@ mod fps;
 
 mod block;
 mod chunk;
+mod config;
+mod noise;
 mod render;
+mod terrain;
 mod world;
 
 #[allow(dead_code)]
@@ -27,6 +30,13 @@ pub fn run() {
         .unwrap_throw();
 }
 
+// The world ticks at a steady rate, however fast frames come, so that fluid flows at the same speed everywhere.
+const TICKS_PER_SECOND: f64 = 20.0;
+// After a long frame, such as when the tab was hidden, the ticks beyond this many are skipped rather than all run at once.
+const MAX_TICKS_PER_FRAME: u32 = 4;
+// This many fluid updates keep a tick short, even while a large lake fills.
+const FLUID_UPDATES_PER_TICK: usize = 2000;
+
 #[derive(Clone)]
 struct State(Rc<RefCell<Model>>);
 
@@ -34,6 +44,8 @@ struct Model {
     animation_frame_closure: js_sys::Function,
     keys: HashSet<String>,
     fps: Option<fps::FrameCounter>,
+    // Ticks owed to the world since the last one, as a fraction of a tick.
+    unticked: f64,
     vr_status: VrStatus,
 
     world: world::World,
@@ -164,16 +176,23 @@ impl State {
                     model.vr_status = VrStatus::RequestedPresentation(display.clone());
                 }
 
+                // Hold control to reach through glass.
+                let translucent = if model.keys.contains("control") {
+                    Translucent::Skip
+                } else {
+                    Translucent::Select
+                };
                 let cast_result = raycast(
                     &model.world,
                     model.player.position,
                     model.player.direction(),
                     5.,
+                    translucent,
                 );
 
                 web_sys::console::log_1(&format!("{:?}", cast_result).into());
                 if let (Some(block), _) = cast_result {
-                    model.set_block(block, block::Block::create(block::BlockName::Stone));
+                    model.set_block(block, block::Block::create(block::BlockId::STONE));
                 }
             }
             Msg::KeyDown(k) => {
@@ -224,11 +243,13 @@ impl State {
 
         if let Some(fps) = &mut model.fps {
             let dt = fps.frame(timestamp);
-            model.info_box.set_inner_text(&format!(
-                "{}\n\n{:?}",
-                fps,
-                model.player.position.as_slice()
-            ));
+            let mut info = format!("{}\n\n{:?}", fps, model.player.position.as_slice());
+            let position = model.player.position.into();
+            if let Some(description) = model.world.describe(position) {
+                info += "\n";
+                info += &description;
+            }
+            model.info_box.set_inner_text(&info);
 
             {
                 let mut movement_vector = nalgebra::Vector4::zeros();
@@ -260,11 +281,16 @@ impl State {
                     .player
                     .r#move(movement_vector * dt as f32, &model.world);
 
-                model.render.update(
-                    &model.world,
-                    render::Msg::PlayerMoved(model.player.position.into()),
-                );
+                model.player_moved();
+            }
+
+            model.unticked += dt * TICKS_PER_SECOND;
+            let ticks = model.unticked.floor();
+            model.unticked -= ticks;
+            for _ in 0..(ticks as u32).min(MAX_TICKS_PER_FRAME) {
+                model.world.tick(FLUID_UPDATES_PER_TICK);
             }
+            model.world_changed();
 
             if let VrStatus::NotFound { three_camera_rot } = &mut model.vr_status {
                 if model.keys.contains("arrowdown") {
@@ -420,42 +446,94 @@ impl Model {
             .dyn_into::<web_sys::WebGl2RenderingContext>()
             .unwrap_throw();
 
+        let config = config::Config::default();
+        let topology = match query_parameter(&window, "topology") {
+            Some(topology) => topology.parse().unwrap_or_else(|e| {
+                web_sys::console::error_1(&format!("Bad topology: {}", e).into());
+                world::Topology::UNBOUNDED
+            }),
+            None => world::Topology::UNBOUNDED,
+        };
+        let generator = {
+            let name = query_parameter(&window, "generator")
+                .unwrap_or_else(|| terrain::GENERATORS[0].name.to_owned());
+            // Without a seed in the URL, pick one at random, and report it so that the world can be visited again.
+            let random_seed = (js_sys::Math::random() * 1e9) as u64;
+            let params = |name: &str| match query_parameter(&window, name) {
+                None if name == "seed" => {
+                    web_sys::console::log_1(&format!("seed={}", random_seed).into());
+                    Some(random_seed.to_string())
+                }
+                value => value,
+            };
+            terrain::create(&name, &params).unwrap_or_else(|e| {
+                web_sys::console::error_1(&format!("Bad generator: {}", e).into());
+                (terrain::GENERATORS[0].create)(&|_| None).unwrap_throw()
+            })
+        };
+
+        let world = world::World::new(
+            config,
+            topology,
+            generator,
+            world::Budget::Bytes(32 << 20),
+            Box::new(std::collections::HashMap::new()),
+        );
+        let player = Player::new(world.spawn_point());
+
         let mut model = Self {
             animation_frame_closure: JsValue::undefined().into(),
             fps: None,
+            unticked: 0.0,
             keys: HashSet::new(),
             vr_status: VrStatus::Searching,
 
-            world: world::World::new(),
-            render: render::Renderer::new(gl),
+            world,
+            render: render::Renderer::new(gl, config),
 
             window,
             document,
             canvas,
             info_box,
 
-            player: Player::new(),
+            player,
         };
 
-        model.render.update(
-            &model.world,
-            render::Msg::PlayerMoved(model.player.position.into()),
-        );
+        model.player_moved();
 
         model
     }
 
-    fn set_block(&mut self, coords: [isize; 4], block: block::Block) {
-        *self.world.get_mut(coords) = block;
+    fn player_moved(&mut self) {
+        let position = self.player.position.into();
+        let (min, max) = self.render.required_chunks(position);
+        self.world.load_chunks(min, max);
+        self.world.evict();
         self.render
-            .update(&self.world, render::Msg::BlockChanged(coords));
+            .update(&self.world, render::Msg::PlayerMoved(position));
+    }
+
+    fn set_block(&mut self, coords: [isize; 4], block: block::Block) {
+        drop(self.world.replace(coords, block));
+    }
+
+    fn world_changed(&mut self) {
+        let changes = self.world.drain_changes();
+        for &block in &changes.blocks {
+            self.render
+                .update(&self.world, render::Msg::BlockChanged(block));
+        }
+        for (which_chunk, cells) in changes.light {
+            self.render
+                .update(&self.world, render::Msg::LightChanged(which_chunk, cells));
+        }
     }
 }
 
 impl Player {
-    fn new() -> Self {
+    fn new(position: [f32; 4]) -> Self {
         Self {
-            position: nalgebra::Vector4::new(0.5, 0.5, 0.5, 0.5),
+            position: position.into(),
             horizontal_orientation: nalgebra::UnitQuaternion::identity(),
             vertical_angle: 0.0,
         }
@@ -474,8 +552,8 @@ impl Player {
         for i in 0..4 {
             new_position_integer[i] = new_position[i].floor() as isize;
         }
-        if world.get(new_position_integer) == block::BlockName::Air {
-            self.position = new_position;
+        if matches!(world.get(new_position_integer), Some(block) if !block.info().solid) {
+            self.position = world.wrap_position(new_position.into()).into();
         }
     }
 
@@ -519,12 +597,37 @@ impl Player {
     }
 }
 
+/// The value of `name` in the query string of the page's URL.
+fn query_parameter(window: &web_sys::Window, name: &str) -> Option<String> {
+    let search = window.location().search().unwrap_throw();
+    search
+        .trim_start_matches('?')
+        .split('&')
+        .filter_map(|pair| {
+            let mut parts = pair.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) if key == name => Some(value.to_owned()),
+                _ => None,
+            }
+        })
+        .next()
+}
+
+/// Whether a raycast stops at solid translucent blocks, such as glass, or passes through them.
+/// Either way, it passes through blocks that are not solid.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Translucent {
+    Skip,
+    Select,
+}
+
 // Note: A translation of this to GLSL is in the fragment shader.
 fn raycast(
     world: &world::World,
     pos: nalgebra::Vector4<f32>,
     dir: nalgebra::Vector4<f32>,
     ray_distance: f32,
+    translucent: Translucent,
 ) -> (Option<[isize; 4]>, Option<[isize; 4]>) {
     let t_max = ray_distance / dir.norm();
 
@@ -554,21 +657,30 @@ fn raycast(
     };
 
     let mut current_block = None;
-    let mut next_block = {
+    let mut next_block = world.cursor({
         let mut out = [0; 4];
         for i in 0..4 {
             out[i] = pos[i].floor() as isize;
         }
         out
-    };
+    });
 
     let mut t = 0.0;
 
     while t < t_max {
-        if world.get(next_block) != block::BlockName::Air {
-            return (current_block, Some(next_block));
+        match next_block.get([0; 4]) {
+            Some(block)
+                if block.info().solid
+                    && (block.info().opaque || translucent == Translucent::Select) =>
+            {
+                return (current_block, Some(next_block.coords()))
+            }
+            // Blocks are only placed where nothing solid is, so skipped glass is never the placement target.
+            Some(block) if !block.info().solid => current_block = Some(next_block.coords()),
+            Some(_) => {}
+            // The ray has left the generated part of the world.
+            None => break,
         }
-        current_block = Some(next_block);
 
         let i: usize = next_ts
             .iter()
@@ -578,7 +690,7 @@ fn raycast(
             .unwrap()
             .0;
 
-        next_block[i] += block_steps[i];
+        next_block.step(i, block_steps[i]);
         t = next_ts[i];
         next_ts[i] += t_steps[i];
     }
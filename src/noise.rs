@@ -0,0 +1,133 @@
+//! Seeded randomness for world generation.
+//! Everything here is a pure function of the seed and the coordinates, so generated chunks don't depend on generation order.
+
+/// Mix `coords` into `seed`, giving a well-distributed 64-bit hash.
+fn hash(seed: u64, coords: &[i64]) -> u64 {
+    let mut h = mix(seed ^ 0x9e37_79b9_7f4a_7c15);
+    for &c in coords {
+        h = mix(h ^ c as u64);
+    }
+    h
+}
+
+// The finalizer of SplitMix64.
+fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Seeded gradient noise. Values lie roughly in `[-1, 1]` and vary smoothly on a scale of one unit.
+#[derive(Debug, Copy, Clone)]
+pub struct Noise {
+    seed: u64,
+}
+
+impl Noise {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    /// An independent noise function, derived from this one's seed.
+    pub fn derive(&self, salt: u64) -> Self {
+        Self::new(hash(self.seed, &[salt as i64]))
+    }
+
+    /// A hash of `coords`, for seeding an [`Rng`].
+    pub fn hash(&self, coords: &[i64]) -> u64 {
+        hash(self.seed, coords)
+    }
+
+    pub fn noise3(&self, p: [f64; 3]) -> f64 {
+        self.gradient(p)
+    }
+
+    pub fn noise4(&self, p: [f64; 4]) -> f64 {
+        self.gradient(p)
+    }
+
+    /// Sum `octaves` layers of noise, each with double the frequency and half the amplitude of the last.
+    /// The result is scaled back into roughly `[-1, 1]`.
+    pub fn fractal3(&self, p: [f64; 3], octaves: u32) -> f64 {
+        let mut total = 0.0;
+        let mut amplitude = 1.0;
+        let mut frequency = 1.0;
+        let mut max = 0.0;
+        for octave in 0..octaves {
+            let layer = self.derive(octave as u64);
+            total +=
+                amplitude * layer.noise3([p[0] * frequency, p[1] * frequency, p[2] * frequency]);
+            max += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.0;
+        }
+        total / max
+    }
+
+    fn gradient<const N: usize>(&self, p: [f64; N]) -> f64 {
+        let mut cell = [0i64; N];
+        let mut offset = [0.0; N];
+        let mut fade = [0.0; N];
+        for i in 0..N {
+            let floor = p[i].floor();
+            cell[i] = floor as i64;
+            offset[i] = p[i] - floor;
+            let t = offset[i];
+            fade[i] = t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+        }
+
+        let mut total = 0.0;
+        for corner in 0..1usize << N {
+            let mut corner_cell = cell;
+            let mut weight = 1.0;
+            let mut corner_offset = offset;
+            for i in 0..N {
+                if corner >> i & 1 == 1 {
+                    corner_cell[i] += 1;
+                    corner_offset[i] -= 1.0;
+                    weight *= fade[i];
+                } else {
+                    weight *= 1.0 - fade[i];
+                }
+            }
+
+            // Each byte of the hash gives one component of the corner's gradient, in [-1, 1].
+            let h = hash(self.seed, &corner_cell);
+            let mut dot = 0.0;
+            for (i, o) in corner_offset.iter().enumerate() {
+                let component = ((h >> (8 * i)) & 0xff) as f64 / 127.5 - 1.0;
+                dot += component * o;
+            }
+            total += weight * dot;
+        }
+
+        // The largest possible dot product is about sqrt(N) / 2 at the centre of a cell, but typical values are much smaller.
+        total * 2.0
+    }
+}
+
+/// A seeded stream of random numbers, for features that are placed somewhere rather than sampled everywhere.
+/// Seed it from [`Noise::hash`] of the feature's location, so the feature doesn't depend on generation order.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        mix(self.0)
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform in `[min, max)`.
+    pub fn range(&mut self, min: f64, max: f64) -> f64 {
+        min + (max - min) * self.next_f64()
+    }
+}
@@ -1,29 +1,39 @@
 mod gl_handler;
 
-use crate::chunk::CHUNK_SIZE;
+use crate::config::Config;
 
-// Changing this value requires changing other parts of the code.
-const RENDER_CHUNKS: usize = 4;
-const RENDER_DISTANCE: usize = ((RENDER_CHUNKS - 1) * CHUNK_SIZE) / 2;
+use std::collections::HashSet;
 
 pub use gl_handler::{Uniforms, Viewport};
 
 pub struct Renderer {
+    config: Config,
     gl_handler: gl_handler::GlHandler,
     mesh_dirty: bool,
 
     corner_of_loaded_region: [isize; 4],
 }
 
+// Past this many changed cells, sending a chunk's whole light texture is cheaper than sending the cells one at a time.
+const MAX_LIGHT_PIXELS: usize = 32;
+
 pub enum Msg {
     BlockChanged([isize; 4]),
+    /// A canonical chunk, and the cells in it whose light changed.
+    LightChanged([isize; 4], HashSet<[isize; 4]>),
     PlayerMoved([f32; 4]),
 }
 
 impl Renderer {
-    pub fn new(gl: web_sys::WebGl2RenderingContext) -> Self {
+    pub fn new(gl: web_sys::WebGl2RenderingContext, config: Config) -> Self {
         Self {
-            gl_handler: gl_handler::GlHandler::new(gl),
+            config,
+            gl_handler: gl_handler::GlHandler::new(
+                gl,
+                config,
+                &crate::block::registry().color_table(),
+                &crate::block::registry().pattern_table(),
+            ),
             mesh_dirty: false,
 
             corner_of_loaded_region: [9999, 9999, 9999, 9999],
@@ -33,15 +43,23 @@ impl Renderer {
     pub fn update(&mut self, world: &crate::world::World, msg: Msg) {
         match msg {
             Msg::BlockChanged(block) => {
-                self.set_block(block, world.get(block));
+                if let Some(name) = world.get(block) {
+                    self.set_block(world.topology(), block, name);
+                }
             }
-            Msg::PlayerMoved(pos) => {
-                let mut chunk = [0; 4];
-                for i in 0..4 {
-                    chunk[i] = (pos[i] as isize - RENDER_DISTANCE as isize)
-                        .div_euclid(CHUNK_SIZE as isize);
+            Msg::LightChanged(which_chunk, cells) => {
+                if cells.len() > MAX_LIGHT_PIXELS {
+                    self.set_chunk_light(world, which_chunk);
+                } else {
+                    for coords in cells {
+                        if let Some(light) = world.light(coords) {
+                            self.set_light(world.topology(), coords, light);
+                        }
+                    }
                 }
-                self.change_loaded_region(world, chunk);
+            }
+            Msg::PlayerMoved(pos) => {
+                self.change_loaded_region(world, self.region_corner(pos));
             }
         }
     }
@@ -60,30 +78,53 @@ impl Renderer {
     pub fn redo_mesh(&mut self, world: &crate::world::World) {
         let mut vertex_data: Vec<f32> = Vec::new();
 
-        fn all_or_nothing(blocks: &[crate::block::BlockName]) -> bool {
-            blocks.iter().all(|b| *b == crate::block::BlockName::Air)
-                || blocks.iter().all(|b| *b != crate::block::BlockName::Air)
+        // The region, plus one block on the negative side of each axis, which the faces below look into.
+        let blocks = {
+            let mut min = [0; 4];
+            let mut max = [0; 4];
+            for i in 0..4 {
+                min[i] = self.corner_of_loaded_region[i] * self.config.chunk_size as isize - 1;
+                max[i] = (self.corner_of_loaded_region[i] + self.config.render_chunks as isize)
+                    * self.config.chunk_size as isize;
+            }
+            world.read_box(min, max, crate::block::BlockId::AIR)
+        };
+        let get = |coords| blocks.get(coords);
+
+        // Faces go wherever blocks that look different meet:
+        // empty space, opaque blocks, and each kind of translucent block.
+        #[derive(PartialEq)]
+        enum Appearance {
+            Empty,
+            Opaque,
+            Translucent(crate::block::BlockId),
+        }
+        fn appearance(block: crate::block::BlockId) -> Appearance {
+            let info = block.info();
+            if info.color.3 == 0.0 {
+                Appearance::Empty
+            } else if info.opaque {
+                Appearance::Opaque
+            } else {
+                Appearance::Translucent(block)
+            }
+        }
+        fn all_alike(blocks: &[crate::block::BlockId]) -> bool {
+            blocks
+                .iter()
+                .all(|&b| appearance(b) == appearance(blocks[0]))
         }
 
-        for x in self.corner_of_loaded_region[0] * CHUNK_SIZE as isize
-            ..(self.corner_of_loaded_region[0] + RENDER_CHUNKS as isize) * CHUNK_SIZE as isize
-        {
-            for y in self.corner_of_loaded_region[1] * CHUNK_SIZE as isize
-                ..(self.corner_of_loaded_region[1] + RENDER_CHUNKS as isize) * CHUNK_SIZE as isize
-            {
-                for z in self.corner_of_loaded_region[2] * CHUNK_SIZE as isize
-                    ..(self.corner_of_loaded_region[2] + RENDER_CHUNKS as isize)
-                        * CHUNK_SIZE as isize
-                {
-                    for w in self.corner_of_loaded_region[3] * CHUNK_SIZE as isize
-                        ..(self.corner_of_loaded_region[3] + RENDER_CHUNKS as isize)
-                            * CHUNK_SIZE as isize
-                    {
-                        if !all_or_nothing(&[
-                            world.get([x, y, z, w]),
-                            world.get([x, y, z, w - 1]),
-                            world.get([x, y, z - 1, w]),
-                            world.get([x, y, z - 1, w - 1]),
+        let [min, max] = [blocks.min(), blocks.max()];
+        for x in min[0] + 1..max[0] {
+            for y in min[1] + 1..max[1] {
+                for z in min[2] + 1..max[2] {
+                    for w in min[3] + 1..max[3] {
+                        if !all_alike(&[
+                            get([x, y, z, w]),
+                            get([x, y, z, w - 1]),
+                            get([x, y, z - 1, w]),
+                            get([x, y, z - 1, w - 1]),
                         ]) {
                             #[rustfmt::skip]
                             vertex_data.extend_from_slice(&[
@@ -96,11 +137,11 @@ impl Renderer {
                             ]);
                         }
 
-                        if !all_or_nothing(&[
-                            world.get([x, y, z, w]),
-                            world.get([x, y, z, w - 1]),
-                            world.get([x, y - 1, z, w]),
-                            world.get([x, y - 1, z, w - 1]),
+                        if !all_alike(&[
+                            get([x, y, z, w]),
+                            get([x, y, z, w - 1]),
+                            get([x, y - 1, z, w]),
+                            get([x, y - 1, z, w - 1]),
                         ]) {
                             #[rustfmt::skip]
                             vertex_data.extend_from_slice(&[
@@ -113,11 +154,11 @@ impl Renderer {
                             ]);
                         }
 
-                        if !all_or_nothing(&[
-                            world.get([x, y, z, w]),
-                            world.get([x, y, z, w - 1]),
-                            world.get([x - 1, y, z, w]),
-                            world.get([x - 1, y, z, w - 1]),
+                        if !all_alike(&[
+                            get([x, y, z, w]),
+                            get([x, y, z, w - 1]),
+                            get([x - 1, y, z, w]),
+                            get([x - 1, y, z, w - 1]),
                         ]) {
                             #[rustfmt::skip]
                             vertex_data.extend_from_slice(&[
@@ -130,11 +171,11 @@ impl Renderer {
                             ]);
                         }
 
-                        if !all_or_nothing(&[
-                            world.get([x, y, z, w]),
-                            world.get([x, y, z - 1, w]),
-                            world.get([x, y - 1, z, w]),
-                            world.get([x, y - 1, z - 1, w]),
+                        if !all_alike(&[
+                            get([x, y, z, w]),
+                            get([x, y, z - 1, w]),
+                            get([x, y - 1, z, w]),
+                            get([x, y - 1, z - 1, w]),
                         ]) {
                             #[rustfmt::skip]
                             vertex_data.extend_from_slice(&[
@@ -147,11 +188,11 @@ impl Renderer {
                             ]);
                         }
 
-                        if !all_or_nothing(&[
-                            world.get([x, y, z, w]),
-                            world.get([x, y, z - 1, w]),
-                            world.get([x - 1, y, z, w]),
-                            world.get([x - 1, y, z - 1, w]),
+                        if !all_alike(&[
+                            get([x, y, z, w]),
+                            get([x, y, z - 1, w]),
+                            get([x - 1, y, z, w]),
+                            get([x - 1, y, z - 1, w]),
                         ]) {
                             #[rustfmt::skip]
                             vertex_data.extend_from_slice(&[
@@ -164,11 +205,11 @@ impl Renderer {
                             ]);
                         }
 
-                        if !all_or_nothing(&[
-                            world.get([x, y, z, w]),
-                            world.get([x, y - 1, z, w]),
-                            world.get([x - 1, y, z, w]),
-                            world.get([x - 1, y - 1, z, w]),
+                        if !all_alike(&[
+                            get([x, y, z, w]),
+                            get([x, y - 1, z, w]),
+                            get([x - 1, y, z, w]),
+                            get([x - 1, y - 1, z, w]),
                         ]) {
                             #[rustfmt::skip]
                             vertex_data.extend_from_slice(&[
@@ -191,7 +232,9 @@ impl Renderer {
 
     fn is_chunk_loaded(&self, chunk: [isize; 4]) -> bool {
         for i in 0..4 {
-            if !(0..CHUNK_SIZE as isize).contains(&(chunk[i] - self.corner_of_loaded_region[i])) {
+            if !(0..self.config.render_chunks as isize)
+                .contains(&(chunk[i] - self.corner_of_loaded_region[i]))
+            {
                 return false;
             }
         }
@@ -200,9 +243,15 @@ impl Renderer {
 
     fn load_chunk(&mut self, world: &crate::world::World, chunk: [isize; 4]) {
         if !self.is_chunk_loaded(chunk) {
-            world.pass_chunk_as_slice(chunk, |slice| {
-                self.gl_handler.set_texture(chunk_texture_loc(chunk), slice)
-            });
+            let loc = self.chunk_texture_loc(chunk);
+            if let Some(data) = world.chunk(chunk) {
+                data.pass_as_slice(|slice| self.gl_handler.set_texture(loc, slice));
+            }
+            if let Some(light) = world.chunk_light(chunk) {
+                light.pass_as_slice(self.config.chunk_volume(), |slice| {
+                    self.gl_handler.set_light(loc, slice)
+                });
+            }
         }
     }
 
@@ -211,10 +260,11 @@ impl Renderer {
             return;
         }
 
-        for x in 0..RENDER_CHUNKS as isize {
-            for y in 0..RENDER_CHUNKS as isize {
-                for z in 0..RENDER_CHUNKS as isize {
-                    for w in 0..RENDER_CHUNKS as isize {
+        let render_chunks = self.config.render_chunks as isize;
+        for x in 0..render_chunks {
+            for y in 0..render_chunks {
+                for z in 0..render_chunks {
+                    for w in 0..render_chunks {
                         self.load_chunk(
                             world,
                             [
@@ -234,27 +284,110 @@ impl Renderer {
         self.mesh_dirty = true;
     }
 
-    fn set_block(&mut self, coords: [isize; 4], block: crate::block::BlockName) {
-        let (which_chunk, rel_pos) = crate::chunk::chunk_position(coords);
-        if self.is_chunk_loaded(which_chunk) {
-            self.gl_handler.set_texture_pixel(
-                chunk_texture_loc(which_chunk),
-                [
-                    rel_pos[0] as usize + CHUNK_SIZE * rel_pos[1] as usize,
-                    rel_pos[2] as usize + CHUNK_SIZE * rel_pos[3] as usize,
-                ],
-                block,
-            );
-
+    // `coords` are canonical. In a wrapping world, the block may appear in several places in the loaded region.
+    fn set_block(
+        &mut self,
+        topology: crate::world::Topology,
+        coords: [isize; 4],
+        block: crate::block::BlockId,
+    ) {
+        for (loc, texture_coordinate) in self.texture_locations(topology, coords) {
+            self.gl_handler
+                .set_texture_pixel(loc, texture_coordinate, block);
             self.mesh_dirty = true;
         }
     }
-}
 
-#[rustfmt::skip]
-fn chunk_texture_loc(chunk: [isize; 4]) -> usize {
-    (chunk[0] as usize & 3) + RENDER_CHUNKS * (
-    (chunk[1] as usize & 3) + RENDER_CHUNKS * (
-    (chunk[2] as usize & 3) + RENDER_CHUNKS * (
-    (chunk[3] as usize & 3))))
+    // `coords` are canonical. The mesh does not depend on light, so it stays as it is.
+    fn set_light(&mut self, topology: crate::world::Topology, coords: [isize; 4], light: u8) {
+        for (loc, texture_coordinate) in self.texture_locations(topology, coords) {
+            self.gl_handler
+                .set_light_pixel(loc, texture_coordinate, light);
+        }
+    }
+
+    // `which_chunk` is canonical.
+    fn set_chunk_light(&mut self, world: &crate::world::World, which_chunk: [isize; 4]) {
+        if let Some(light) = world.chunk_light(which_chunk) {
+            for loc in self.chunk_texture_locs(world.topology(), which_chunk) {
+                light.pass_as_slice(self.config.chunk_volume(), |slice| {
+                    self.gl_handler.set_light(loc, slice)
+                });
+            }
+        }
+    }
+
+    // Every place in the textures where the cell at canonical `coords` appears.
+    fn texture_locations(
+        &self,
+        topology: crate::world::Topology,
+        coords: [isize; 4],
+    ) -> Vec<(usize, [usize; 2])> {
+        let (which_chunk, rel_pos) = self.config.chunk_position(coords);
+        let chunk_size = self.config.chunk_size;
+        let texture_coordinate = [
+            rel_pos[0] as usize + chunk_size * rel_pos[1] as usize,
+            rel_pos[2] as usize + chunk_size * rel_pos[3] as usize,
+        ];
+        self.chunk_texture_locs(topology, which_chunk)
+            .into_iter()
+            .map(|loc| (loc, texture_coordinate))
+            .collect()
+    }
+
+    // Every texture layer holding a copy of the canonical chunk `which_chunk`.
+    fn chunk_texture_locs(
+        &self,
+        topology: crate::world::Topology,
+        which_chunk: [isize; 4],
+    ) -> Vec<usize> {
+        let mut out = Vec::new();
+        let render_chunks = self.config.render_chunks as isize;
+        let corner = self.corner_of_loaded_region;
+        for x in 0..render_chunks {
+            for y in 0..render_chunks {
+                for z in 0..render_chunks {
+                    for w in 0..render_chunks {
+                        let chunk = [corner[0] + x, corner[1] + y, corner[2] + z, corner[3] + w];
+                        if topology.canonical_chunk(chunk) == Some(which_chunk) {
+                            out.push(self.chunk_texture_loc(chunk));
+                        }
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    /// The chunks that must be generated before the renderer is told that the player moved to `pos`.
+    /// This includes a layer of chunks on the negative side of the rendered region, which the mesh looks into.
+    pub fn required_chunks(&self, pos: [f32; 4]) -> ([isize; 4], [isize; 4]) {
+        let corner = self.region_corner(pos);
+        let mut min = [0; 4];
+        let mut max = [0; 4];
+        for i in 0..4 {
+            min[i] = corner[i] - 1;
+            max[i] = corner[i] + self.config.render_chunks as isize;
+        }
+        (min, max)
+    }
+
+    fn region_corner(&self, pos: [f32; 4]) -> [isize; 4] {
+        let mut chunk = [0; 4];
+        for i in 0..4 {
+            chunk[i] = (pos[i] as isize - self.config.render_distance() as isize)
+                .div_euclid(self.config.chunk_size as isize);
+        }
+        chunk
+    }
+
+    // Chunks are stored in the texture array modulo `render_chunks`, so that moving only requires uploading the new chunks.
+    fn chunk_texture_loc(&self, chunk: [isize; 4]) -> usize {
+        let render_chunks = self.config.render_chunks;
+        let mut loc = 0;
+        for i in (0..4).rev() {
+            loc = loc * render_chunks + (chunk[i] as usize & (render_chunks - 1));
+        }
+        loc
+    }
 }
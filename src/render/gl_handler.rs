@@ -1,4 +1,4 @@
-use crate::chunk::CHUNK_SIZE;
+use crate::config::Config;
 
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
@@ -7,10 +7,14 @@ type GL = web_sys::WebGl2RenderingContext;
 
 pub struct GlHandler {
     gl: GL,
+    config: Config,
     program: web_sys::WebGlProgram,
     vao: web_sys::WebGlVertexArrayObject,
     vertex_buffer: web_sys::WebGlBuffer,
     world_tex: web_sys::WebGlTexture,
+    light_tex: web_sys::WebGlTexture,
+    block_colors: web_sys::WebGlTexture,
+    block_patterns: web_sys::WebGlTexture,
 
     num_triangles: usize,
 }
@@ -21,6 +25,9 @@ impl Drop for GlHandler {
         self.gl.delete_vertex_array(Some(&self.vao));
         self.gl.delete_buffer(Some(&self.vertex_buffer));
         self.gl.delete_texture(Some(&self.world_tex));
+        self.gl.delete_texture(Some(&self.light_tex));
+        self.gl.delete_texture(Some(&self.block_colors));
+        self.gl.delete_texture(Some(&self.block_patterns));
     }
 }
 
@@ -34,12 +41,13 @@ pub struct Uniforms {
 }
 
 impl GlHandler {
-    pub fn new(gl: GL) -> Self {
+    /// `block_colors` is the RGBA color of each of the 256 block IDs, and `block_patterns` is the pattern of each.
+    pub fn new(gl: GL, config: Config, block_colors: &[u8], block_patterns: &[u8]) -> Self {
         // Multiplicative Blending
         gl.enable(GL::BLEND);
         gl.blend_func(GL::DST_COLOR, GL::ZERO);
 
-        let program = compile_program(&gl);
+        let program = compile_program(&gl, config);
 
         let vao = gl.create_vertex_array().unwrap_throw();
         gl.bind_vertex_array(Some(&vao));
@@ -58,50 +66,23 @@ impl GlHandler {
             GL::STATIC_DRAW,
         );
 
-        let world_tex = gl.create_texture().unwrap_throw();
-        gl.bind_texture(GL::TEXTURE_2D_ARRAY, Some(&world_tex));
         gl.pixel_storei(GL::UNPACK_ALIGNMENT, 1);
-        gl.tex_image_3d_with_opt_u8_array(
-            GL::TEXTURE_2D_ARRAY,
-            0,                                // level
-            GL::R8UI as i32,                  // internal_format
-            (CHUNK_SIZE * CHUNK_SIZE) as i32, // width
-            (CHUNK_SIZE * CHUNK_SIZE) as i32, // height
-            256,                              // depth
-            0,                                // border
-            GL::RED_INTEGER,                  // format
-            GL::UNSIGNED_BYTE,                // type
-            Some(&[0; 256 * CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE]),
-        )
-        .unwrap_throw();
-
-        gl.tex_parameteri(
-            GL::TEXTURE_2D_ARRAY,
-            GL::TEXTURE_MIN_FILTER,
-            GL::NEAREST as i32,
-        );
-        gl.tex_parameteri(
-            GL::TEXTURE_2D_ARRAY,
-            GL::TEXTURE_MAG_FILTER,
-            GL::NEAREST as i32,
-        );
-        gl.tex_parameteri(
-            GL::TEXTURE_2D_ARRAY,
-            GL::TEXTURE_WRAP_S,
-            GL::CLAMP_TO_EDGE as i32,
-        );
-        gl.tex_parameteri(
-            GL::TEXTURE_2D_ARRAY,
-            GL::TEXTURE_WRAP_T,
-            GL::CLAMP_TO_EDGE as i32,
-        );
+        let world_tex = create_chunk_texture(&gl, config);
+        let light_tex = create_chunk_texture(&gl, config);
+
+        let block_colors = create_table_texture(&gl, GL::RGBA8, GL::RGBA, block_colors);
+        let block_patterns = create_table_texture(&gl, GL::R8UI, GL::RED_INTEGER, block_patterns);
 
         Self {
             gl,
+            config,
             program,
             vao,
             vertex_buffer,
             world_tex,
+            light_tex,
+            block_colors,
+            block_patterns,
 
             num_triangles: 0,
         }
@@ -120,23 +101,7 @@ impl GlHandler {
     }
 
     pub fn set_texture(&self, which_texture: usize, blocks: &[u8]) {
-        self.gl
-            .bind_texture(GL::TEXTURE_2D_ARRAY, Some(&self.world_tex));
-        self.gl
-            .tex_sub_image_3d_with_opt_u8_array(
-                GL::TEXTURE_2D_ARRAY,
-                0,
-                0,
-                0,
-                which_texture as i32,
-                (CHUNK_SIZE * CHUNK_SIZE) as i32,
-                (CHUNK_SIZE * CHUNK_SIZE) as i32,
-                1,
-                GL::RED_INTEGER,
-                GL::UNSIGNED_BYTE,
-                Some(blocks),
-            )
-            .unwrap_throw();
+        self.write_chunk_texture(&self.world_tex, which_texture, [0, 0], None, blocks);
     }
 
     /// Does not bounds-check texture_coordinate.
@@ -144,23 +109,58 @@ impl GlHandler {
         &self,
         which_texture: usize,
         texture_coordinate: [usize; 2],
-        block: crate::block::BlockName,
+        block: crate::block::BlockId,
     ) {
-        self.gl
-            .bind_texture(GL::TEXTURE_2D_ARRAY, Some(&self.world_tex));
+        self.write_chunk_texture(
+            &self.world_tex,
+            which_texture,
+            texture_coordinate,
+            Some(1),
+            &[block.0],
+        );
+    }
+
+    /// Like `set_texture`, but for the light level of each cell.
+    pub fn set_light(&self, which_texture: usize, light: &[u8]) {
+        self.write_chunk_texture(&self.light_tex, which_texture, [0, 0], None, light);
+    }
+
+    /// Does not bounds-check texture_coordinate.
+    pub fn set_light_pixel(&self, which_texture: usize, texture_coordinate: [usize; 2], light: u8) {
+        self.write_chunk_texture(
+            &self.light_tex,
+            which_texture,
+            texture_coordinate,
+            Some(1),
+            &[light],
+        );
+    }
+
+    // Write a square of side `size` into one layer of a texture made by `create_chunk_texture`,
+    // or the whole layer if `size` is `None`.
+    fn write_chunk_texture(
+        &self,
+        texture: &web_sys::WebGlTexture,
+        which_texture: usize,
+        offset: [usize; 2],
+        size: Option<usize>,
+        data: &[u8],
+    ) {
+        let size = size.unwrap_or(self.config.chunk_size * self.config.chunk_size) as i32;
+        self.gl.bind_texture(GL::TEXTURE_2D_ARRAY, Some(texture));
         self.gl
             .tex_sub_image_3d_with_opt_u8_array(
                 GL::TEXTURE_2D_ARRAY,
                 0,
-                texture_coordinate[0] as i32,
-                texture_coordinate[1] as i32,
+                offset[0] as i32,
+                offset[1] as i32,
                 which_texture as i32,
-                1,
-                1,
+                size,
+                size,
                 1,
                 GL::RED_INTEGER,
                 GL::UNSIGNED_BYTE,
-                Some(&[block as u8]),
+                Some(data),
             )
             .unwrap_throw();
     }
@@ -220,6 +220,36 @@ impl GlHandler {
             0,
         );
 
+        self.gl.active_texture(GL::TEXTURE1);
+        self.gl
+            .bind_texture(GL::TEXTURE_2D, Some(&self.block_colors));
+        self.gl.uniform1i(
+            self.gl
+                .get_uniform_location(&self.program, "block_colors")
+                .as_ref(),
+            1,
+        );
+        self.gl.active_texture(GL::TEXTURE2);
+        self.gl
+            .bind_texture(GL::TEXTURE_2D_ARRAY, Some(&self.light_tex));
+        self.gl.uniform1i(
+            self.gl
+                .get_uniform_location(&self.program, "light")
+                .as_ref(),
+            2,
+        );
+
+        self.gl.active_texture(GL::TEXTURE3);
+        self.gl
+            .bind_texture(GL::TEXTURE_2D, Some(&self.block_patterns));
+        self.gl.uniform1i(
+            self.gl
+                .get_uniform_location(&self.program, "block_patterns")
+                .as_ref(),
+            3,
+        );
+        self.gl.active_texture(GL::TEXTURE0);
+
         self.gl.uniform_matrix4fv_with_f32_array(
             self.gl
                 .get_uniform_location(&self.program, "three_camera")
@@ -269,7 +299,78 @@ impl GlHandler {
     }
 }
 
-fn compile_program(gl: &GL) -> web_sys::WebGlProgram {
+// One entry for each of the 256 block IDs.
+fn create_table_texture(
+    gl: &GL,
+    internal_format: u32,
+    format: u32,
+    data: &[u8],
+) -> web_sys::WebGlTexture {
+    let texture = gl.create_texture().unwrap_throw();
+    gl.bind_texture(GL::TEXTURE_2D, Some(&texture));
+    gl.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+        GL::TEXTURE_2D,
+        0,                      // level
+        internal_format as i32, // internal_format
+        256,                    // width
+        1,                      // height
+        0,                      // border
+        format,                 // format
+        GL::UNSIGNED_BYTE,      // type
+        Some(data),
+    )
+    .unwrap_throw();
+    gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_MIN_FILTER, GL::NEAREST as i32);
+    gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_MAG_FILTER, GL::NEAREST as i32);
+    texture
+}
+
+// One byte per cell of every chunk in the region.
+// One layer per chunk, with x and y along the width, and z and w along the height.
+fn create_chunk_texture(gl: &GL, config: Config) -> web_sys::WebGlTexture {
+    let chunk_side = (config.chunk_size * config.chunk_size) as i32;
+    let layers = config.render_chunks.pow(4);
+
+    let texture = gl.create_texture().unwrap_throw();
+    gl.bind_texture(GL::TEXTURE_2D_ARRAY, Some(&texture));
+    gl.tex_image_3d_with_opt_u8_array(
+        GL::TEXTURE_2D_ARRAY,
+        0,                 // level
+        GL::R8UI as i32,   // internal_format
+        chunk_side,        // width
+        chunk_side,        // height
+        layers as i32,     // depth
+        0,                 // border
+        GL::RED_INTEGER,   // format
+        GL::UNSIGNED_BYTE, // type
+        Some(&vec![0; layers * config.chunk_volume()]),
+    )
+    .unwrap_throw();
+
+    gl.tex_parameteri(
+        GL::TEXTURE_2D_ARRAY,
+        GL::TEXTURE_MIN_FILTER,
+        GL::NEAREST as i32,
+    );
+    gl.tex_parameteri(
+        GL::TEXTURE_2D_ARRAY,
+        GL::TEXTURE_MAG_FILTER,
+        GL::NEAREST as i32,
+    );
+    gl.tex_parameteri(
+        GL::TEXTURE_2D_ARRAY,
+        GL::TEXTURE_WRAP_S,
+        GL::CLAMP_TO_EDGE as i32,
+    );
+    gl.tex_parameteri(
+        GL::TEXTURE_2D_ARRAY,
+        GL::TEXTURE_WRAP_T,
+        GL::CLAMP_TO_EDGE as i32,
+    );
+    texture
+}
+
+fn compile_program(gl: &GL, config: Config) -> web_sys::WebGlProgram {
     let vertex_shader = gl.create_shader(GL::VERTEX_SHADER).unwrap_throw();
     gl.shader_source(&vertex_shader, include_str!("shaders/vertex.glsl"));
     gl.compile_shader(&vertex_shader);
@@ -281,9 +382,10 @@ fn compile_program(gl: &GL) -> web_sys::WebGlProgram {
         &fragment_shader,
         &format!(
             include_str!("shaders/fragment.glsl"),
-            CHUNK_SIZE,
-            super::RENDER_CHUNKS,
-            super::RENDER_DISTANCE,
+            chunk_size = config.chunk_size,
+            chunk_bits = config.chunk_bits(),
+            render_chunks = config.render_chunks,
+            render_distance = config.render_distance(),
         ),
     );
     gl.compile_shader(&fragment_shader);
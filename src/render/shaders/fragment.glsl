@@ -1,6 +1,7 @@
 #version 300 es
 precision mediump float;
 precision mediump usampler2DArray;
+precision mediump usampler2D;
 
 in vec4 vpos;
 in vec4 v_three_screen_pos;
@@ -15,46 +16,105 @@ uniform vec4 tiny_three_camera_fleeing_step_in_world_coordinates_a;
 uniform float tiny_three_camera_fleeing_step_in_world_coordinates_b;
 
 uniform usampler2DArray world;
+// The color of each block ID, from the block registry. Blocks with zero alpha are empty space.
+uniform sampler2D block_colors;
+// The pattern of each block ID, numbered as in `Pattern` in the block registry.
+uniform usampler2D block_patterns;
+// The light of each cell, laid out like `world`.
+// The low four bits are the block light, and the high four bits are the sky light, each from 0 to 15.
+uniform usampler2DArray light;
 
-int chunk_size = {};
-int render_chunks = {};
-float render_distance = float({});
+// Substituted from `Config` when the shader is compiled.
+const int chunk_size = {chunk_size};
+const int chunk_bits = {chunk_bits};
+const int render_chunks = {render_chunks};
+const float render_distance = float({render_distance});
 
 
 vec4 block_color(uint id) {{
-    if (id == uint(0)) {{
-        return vec4(1., 1., 1., 0.);
-    }} else if (id == uint(1)) {{
-        return vec4(0.5, 0.5, 0.5, 1.);
-    }} else if (id == uint(2)) {{
-        return vec4(0.0, 0.8, 0.0, 1.);
-    }} else {{
-        return vec4(1.0, 0.0, 1.0, 1.0);
+    return texelFetch(block_colors, ivec2(id, 0), 0);
+}}
+
+const uint PLAIN = 0u;
+const uint GRID = 1u;
+const uint SPECKLE = 2u;
+const uint BLADES = 3u;
+
+// A pseudorandom number from 0 to 1 for each cell.
+float random(ivec4 cell) {{
+    // Hashing needs all 32 bits.
+    highp uvec4 v = uvec4(cell);
+    highp uint h = v.x * 0x8da6b343u ^ v.y * 0xd8163841u ^ v.z * 0xcb1ab31fu ^ v.w * 0x165667b1u;
+    h ^= h >> 15;
+    h *= 0x2c1b3c6du;
+    h ^= h >> 12;
+    return float(h & 0xffffu) / 65535.0;
+}}
+
+// Draw the block's pattern over `color`, on the face of `block` that was entered across `axis`.
+// `pos` is the point on the face.
+vec4 pattern_color(uint id, vec4 color, ivec4 block, vec4 pos, int axis) {{
+    uint pattern = texelFetch(block_patterns, ivec2(id, 0), 0).r;
+
+    // Where on the face the point is. The crossed coordinate is set to zero, so that it cannot flicker between 0 and 1.
+    vec4 local = clamp(pos - vec4(block), 0.0, 0.999);
+    local[axis] = 0.0;
+
+    if (pattern == GRID) {{
+        // The face is three-dimensional, so its edges are where any of its other three coordinates is near 0 or 1.
+        vec4 edge = min(local, 1.0 - local);
+        edge[axis] = 1.0;
+        float nearest = min(min(edge.x, edge.y), min(edge.z, edge.w));
+        color.rgb *= nearest < 0.06 ? 0.6 : 1.0;
+    }} else if (pattern == SPECKLE || (pattern == BLADES && axis == 3)) {{
+        color.rgb *= mix(0.8, 1.15, random(block * 8 + ivec4(local * 8.0)));
+    }} else if (pattern == BLADES) {{
+        // Blades hang down from the top of the block, each of random length.
+        vec4 across = local;
+        across.w = 0.0;
+        float blade = random(block * 8 + ivec4(across * 8.0));
+        color.rgb *= 1.0 - local.w < mix(0.2, 0.6, blade) ? 1.1 : 0.7;
     }}
+    return color;
 }}
 
-uint get_block(ivec4 pos) {{
+// Walls and wrapping need no special handling here.
+// Each texture layer holds whichever chunk its part of the region stands for,
+// and chunks beyond a wall are uploaded as barrier blocks.
+ivec3 texel(ivec4 pos) {{
 
-    ivec4 chunk = pos >> 3;
-    pos &= 7;
-    chunk &= 3;
+    ivec4 chunk = pos >> chunk_bits;
+    pos &= chunk_size - 1;
+    chunk &= render_chunks - 1;
 
     ivec2 tex_coord = ivec2(pos.x + chunk_size*pos.y, pos.z + chunk_size*pos.w);
     int tex_layer = chunk.x + render_chunks * (chunk.y + render_chunks * (chunk.z + render_chunks*chunk.w));
 
-    return texelFetch(world, ivec3(tex_coord, tex_layer), 0).r;
+    return ivec3(tex_coord, tex_layer);
+}}
+
+uint get_block(ivec4 pos) {{
+    return texelFetch(world, texel(pos), 0).r;
+}}
+
+// The brighter of the two kinds of light, from 0 to 1.
+float get_light(ivec4 pos) {{
+    uint level = texelFetch(light, texel(pos), 0).r;
+    return float(max(level & 15u, level >> 4)) / 15.0;
 }}
 
 
 // Raytrace along the ray from `start` through `end`.
+// The ray passes through translucent blocks, taking on their tint, until it enters one at a parameter of at least `t_translucent`.
 // If the ray intersects the scene at `mix(start, end, parameter)`:
 //     Set t = parameter.
-//     Set col = the color of the surface at the intersection.
+//     Set col = the color of the surface at the intersection, with its pattern, as seen through any translucent blocks on the way.
+//     Set brightness = the light level of the cell in front of the surface, from 0 to 1.
 //     Return true;
 // If there is no intersection, return false.
 
-// Note: A translation of this into Rust is in `world.rs`.
-bool intersect_scene(vec4 start, vec4 end, out float t, out vec4 col) {{
+// Note: A translation of this into Rust is in `lib.rs`.
+bool intersect_scene(vec4 start, vec4 end, float t_translucent, out float t, out vec4 col, out float brightness) {{
 
     float t_max = render_distance / length(end - start);
 
@@ -63,33 +123,50 @@ bool intersect_scene(vec4 start, vec4 end, out float t, out vec4 col) {{
 
     ivec4 block_steps = ivec4(sign(end - start));
     ivec4 current_block = ivec4(floor(start));
+    ivec4 previous_block = current_block;
+    // The axis along which the ray last crossed into another block.
+    int axis = 3;
+
+    vec4 tint = vec4(1.0);
+    vec4 current_color = block_color(get_block(current_block));
 
     t = 0.0;
-    while (t < t_max && get_block(current_block) == uint(0)) {{
+    while (t < t_max && current_color.a < 1.0 && (current_color.a == 0.0 || t < t_translucent)) {{
+        tint *= mix(vec4(1.0), current_color, current_color.a);
+        previous_block = current_block;
+
         if (min(next_ts.x, next_ts.y) < min(next_ts.z, next_ts.w)) {{
             if (next_ts.x < next_ts.y) {{
                 t = next_ts.x;
+                axis = 0;
                 next_ts.x += t_steps.x;
                 current_block.x += block_steps.x;
             }} else {{
                 t = next_ts.y;
+                axis = 1;
                 next_ts.y += t_steps.y;
                 current_block.y += block_steps.y;
             }}
         }} else {{
             if (next_ts.z < next_ts.w) {{
                 t = next_ts.z;
+                axis = 2;
                 next_ts.z += t_steps.z;
                 current_block.z += block_steps.z;
             }} else {{
                 t = next_ts.w;
+                axis = 3;
                 next_ts.w += t_steps.w;
                 current_block.w += block_steps.w;
             }}
         }}
+        current_color = block_color(get_block(current_block));
     }}
 
-    col = block_color(get_block(current_block));
+    vec4 surface = pattern_color(get_block(current_block), current_color, current_block, mix(start, end, t), axis);
+    col = tint * mix(vec4(1.0), surface, current_color.a);
+    col.a = 1.0;
+    brightness = get_light(previous_block);
 
     return t < t_max;
 
@@ -110,12 +187,16 @@ void main() {{
     vec4 adjusted_pos = (vpos + tiny_three_camera_fleeing_step_in_world_coordinates_a) / (1.0 + tiny_three_camera_fleeing_step_in_world_coordinates_b);
 
     float t;
-    if (intersect_scene(four_camera_pos, adjusted_pos, t, color)) {{
+    float brightness;
+    // This fragment lies on the face at t = 1; anything hit before that is in front of the face.
+    if (intersect_scene(four_camera_pos, adjusted_pos, 0.993, t, color, brightness)) {{
         if (t < 0.993) {{
             // Occluded
             discard;
         }}
-        color = mix(color, vec4(1.0), 0.8);
+        // Every face is drawn over the ones behind it, so faces are faded towards white to keep the scene from going dark.
+        // Lit faces are faded less, and so stand out.
+        color = mix(color, vec4(1.0), mix(0.9, 0.6, brightness));
     }} else {{
         // Sky
         color = vec4(0.8, 0.9, 1.0, 1.0);
@@ -0,0 +1,176 @@
+mod classic;
+mod flat;
+mod maze;
+mod natural;
+#[cfg(test)]
+mod tests;
+
+use crate::block::BlockId;
+use crate::chunk::Chunk;
+
+/// Decides what chunks contain when they are first generated.
+///
+/// A chunk's contents must depend only on its coordinates and the generator's settings,
+/// so that chunks come out the same whatever order they are generated in, and when regenerated after eviction.
+pub trait TerrainGenerator: Send + Sync {
+    fn generate(&self, chunk_size: usize, which_chunk: [isize; 4]) -> Chunk;
+
+    /// The highest `w` at which the generator puts an opaque block in the `w` column at `xyz`, if it knows.
+    /// Sky light treats everything above it as open sky until the chunks there are loaded,
+    /// so it may leave out things standing on the ground, but should not be below the ground itself.
+    fn surface(&self, _column: [isize; 3]) -> Option<isize> {
+        None
+    }
+
+    /// Where the player starts.
+    fn spawn_point(&self) -> [f32; 4] {
+        [0.5, 0.5, 0.5, 0.5]
+    }
+
+    /// Something to tell the player about where they are, such as which biome they are in.
+    fn describe(&self, _position: [f32; 4]) -> Option<String> {
+        None
+    }
+}
+
+/// Looks up settings for a generator, such as its seed.
+/// On the page, these come from the URL's query string.
+pub type Params<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+pub struct Entry {
+    pub name: &'static str,
+    pub create: fn(Params) -> Result<Box<dyn TerrainGenerator>, String>,
+}
+
+/// Every generator that can be picked by name. The first is the default.
+pub const GENERATORS: &[Entry] = &[
+    Entry {
+        name: "classic",
+        create: |_| Ok(Box::new(classic::Classic)),
+    },
+    Entry {
+        name: "natural",
+        create: |params| {
+            let sea_level = match params("sea_level") {
+                Some(level) => level
+                    .parse()
+                    .map_err(|_| format!("invalid sea level {:?}", level))?,
+                None => natural::DEFAULT_SEA_LEVEL,
+            };
+            Ok(Box::new(natural::Natural::new(seed(params)?, sea_level)))
+        },
+    },
+    Entry {
+        name: "maze",
+        create: |params| {
+            let size = match params("size") {
+                Some(size) => maze::parse_size(&size)?,
+                None => [4; 4],
+            };
+            Ok(Box::new(maze::Maze::new(seed(params)?, size)?))
+        },
+    },
+    Entry {
+        name: "flat",
+        create: |params| {
+            let flat = match (params("layers"), params("preset")) {
+                (Some(_), Some(_)) => {
+                    return Err("give either layers or a preset, not both".to_owned())
+                }
+                (Some(layers), None) => layers.parse(),
+                (None, Some(preset)) => flat::Flat::preset(&preset),
+                (None, None) => flat::Flat::preset("default"),
+            };
+            match flat {
+                Ok(flat) => Ok(Box::new(flat)),
+                Err(e) => Err(format!("invalid flat world: {}", e)),
+            }
+        },
+    },
+];
+
+pub fn create(name: &str, params: Params) -> Result<Box<dyn TerrainGenerator>, String> {
+    match GENERATORS.iter().find(|entry| entry.name == name) {
+        Some(entry) => (entry.create)(params),
+        None => Err(format!(
+            "unknown generator {:?}; the generators are {:?}",
+            name,
+            GENERATORS
+                .iter()
+                .map(|entry| entry.name)
+                .collect::<Vec<_>>()
+        )),
+    }
+}
+
+/// The `seed` parameter, which defaults to zero.
+pub fn seed(params: Params) -> Result<u64, String> {
+    match params("seed") {
+        Some(seed) => seed.parse().map_err(|_| format!("invalid seed {:?}", seed)),
+        None => Ok(0),
+    }
+}
+
+/// A chunk under construction, addressed by world coordinates.
+pub struct ChunkBuilder {
+    size: usize,
+    // The world coordinates of the chunk's cell `[0, 0, 0, 0]`.
+    origin: [isize; 4],
+    blocks: Vec<BlockId>,
+}
+
+impl ChunkBuilder {
+    pub fn new(chunk_size: usize, which_chunk: [isize; 4], fill: BlockId) -> Self {
+        let mut origin = [0; 4];
+        for i in 0..4 {
+            origin[i] = which_chunk[i] * chunk_size as isize;
+        }
+        Self {
+            size: chunk_size,
+            origin,
+            blocks: vec![fill; chunk_size.pow(4)],
+        }
+    }
+
+    /// The world coordinates of the cells `b` in this chunk satisfy `min()[i] <= b[i] < max()[i]`.
+    pub fn min(&self) -> [isize; 4] {
+        self.origin
+    }
+
+    pub fn max(&self) -> [isize; 4] {
+        let mut out = self.origin;
+        for x in out.iter_mut() {
+            *x += self.size as isize;
+        }
+        out
+    }
+
+    fn index(&self, coords: [isize; 4]) -> Option<usize> {
+        let mut index = 0;
+        for i in (0..4).rev() {
+            let rel = coords[i] - self.origin[i];
+            if !(0..self.size as isize).contains(&rel) {
+                return None;
+            }
+            index = index * self.size + rel as usize;
+        }
+        Some(index)
+    }
+
+    /// `None` if `coords` is outside the chunk.
+    pub fn get(&self, coords: [isize; 4]) -> Option<BlockId> {
+        self.index(coords).map(|index| self.blocks[index])
+    }
+
+    /// Does nothing if `coords` is outside the chunk.
+    /// This lets features that span several chunks draw themselves without clipping.
+    pub fn set(&mut self, coords: [isize; 4], block: BlockId) {
+        if let Some(index) = self.index(coords) {
+            self.blocks[index] = block;
+        }
+    }
+
+    pub fn build(self) -> Chunk {
+        Chunk::from_blocks(self.size, &self.blocks)
+    }
+}
@@ -0,0 +1,44 @@
+use super::{ChunkBuilder, TerrainGenerator};
+use crate::block::BlockId;
+use crate::chunk::Chunk;
+
+/// The original test world: a small slab of stone and grass, with a tunnel through it.
+pub struct Classic;
+
+impl TerrainGenerator for Classic {
+    fn generate(&self, chunk_size: usize, which_chunk: [isize; 4]) -> Chunk {
+        let mut chunk = ChunkBuilder::new(chunk_size, which_chunk, BlockId::AIR);
+        let [min, max] = [chunk.min(), chunk.max()];
+        for x in min[0]..max[0] {
+            for y in min[1]..max[1] {
+                for z in min[2]..max[2] {
+                    for w in min[3]..max[3] {
+                        chunk.set([x, y, z, w], generate_block([x, y, z, w]));
+                    }
+                }
+            }
+        }
+        chunk.build()
+    }
+
+    fn surface(&self, column: [isize; 3]) -> Option<isize> {
+        match column {
+            [-1..=4, -1..=4, -1..=4] => Some(1),
+            _ => None,
+        }
+    }
+}
+
+fn generate_block(block: [isize; 4]) -> BlockId {
+    match block {
+        [-1..=3, 0, 0, 0]
+        | [3, 0..=3, 0, 0]
+        | [3, 3, 0..=3, 0]
+        | [0..=3, 3, 3, 0]
+        | [0, 0..=3, 3, 0]
+        | [0, 0, 0..=3, 0] => BlockId::AIR,
+        [-1..=4, -1..=4, -1..=4, -1..=0] => BlockId::STONE,
+        [-1..=4, -1..=4, -1..=4, 1] => BlockId::GRASS,
+        _ => BlockId::AIR,
+    }
+}
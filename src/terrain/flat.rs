@@ -0,0 +1,130 @@
+use super::{ChunkBuilder, TerrainGenerator};
+use crate::block::{registry, BlockId};
+use crate::chunk::Chunk;
+
+/// Flat ground made of horizontal layers, with air above.
+///
+/// The layers are written bottom to top, such as `stone*5,dirt*2,grass`.
+/// The first layer is at `w = 0`, and repeats forever below it.
+pub struct Flat {
+    layers: Vec<BlockId>,
+}
+
+// Far taller than anyone will build, while keeping the layer list small.
+const MAX_LAYERS: usize = 4096;
+
+/// Layer strings that can be picked by name.
+pub const PRESETS: &[(&str, &str)] = &[
+    ("default", "stone*5,dirt*2,grass"),
+    ("desert", "stone*4,sand*3"),
+    ("tundra", "stone*5,dirt,snow"),
+    ("arena", "barrier,stone*2,planks"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    NoLayers,
+    UnknownBlock(String),
+    InvalidCount(String),
+    TooManyLayers,
+    UnknownPreset(String),
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseError::NoLayers => write!(f, "there are no layers"),
+            ParseError::UnknownBlock(name) => write!(f, "unknown block {:?}", name),
+            ParseError::InvalidCount(count) => write!(
+                f,
+                "invalid layer count {:?}; expected a positive number",
+                count
+            ),
+            ParseError::TooManyLayers => write!(f, "there are more than {} layers", MAX_LAYERS),
+            ParseError::UnknownPreset(name) => write!(
+                f,
+                "unknown preset {:?}; the presets are {:?}",
+                name,
+                PRESETS.iter().map(|&(name, _)| name).collect::<Vec<_>>()
+            ),
+        }
+    }
+}
+
+impl Flat {
+    pub fn preset(name: &str) -> Result<Self, ParseError> {
+        match PRESETS.iter().find(|&&(preset, _)| preset == name) {
+            Some((_, layers)) => layers.parse(),
+            None => Err(ParseError::UnknownPreset(name.to_owned())),
+        }
+    }
+
+    fn layer(&self, w: isize) -> BlockId {
+        if w < 0 {
+            self.layers[0]
+        } else {
+            self.layers.get(w as usize).copied().unwrap_or(BlockId::AIR)
+        }
+    }
+}
+
+impl std::str::FromStr for Flat {
+    type Err = ParseError;
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        let mut layers = Vec::new();
+        for layer in s
+            .split(',')
+            .map(str::trim)
+            .filter(|layer| !layer.is_empty())
+        {
+            let mut parts = layer.splitn(2, '*');
+            let name = parts.next().unwrap_or("").trim();
+            let block = registry()
+                .id(name)
+                .ok_or_else(|| ParseError::UnknownBlock(name.to_owned()))?;
+            let count = match parts.next() {
+                Some(count) => match count.trim().parse::<usize>() {
+                    Ok(count) if count > 0 => count,
+                    _ => return Err(ParseError::InvalidCount(count.to_owned())),
+                },
+                None => 1,
+            };
+            if count > MAX_LAYERS - layers.len() {
+                return Err(ParseError::TooManyLayers);
+            }
+            layers.resize(layers.len() + count, block);
+        }
+        if layers.is_empty() {
+            return Err(ParseError::NoLayers);
+        }
+        Ok(Flat { layers })
+    }
+}
+
+impl TerrainGenerator for Flat {
+    fn generate(&self, chunk_size: usize, which_chunk: [isize; 4]) -> Chunk {
+        let mut chunk = ChunkBuilder::new(chunk_size, which_chunk, BlockId::AIR);
+        let [min, max] = [chunk.min(), chunk.max()];
+        for x in min[0]..max[0] {
+            for y in min[1]..max[1] {
+                for z in min[2]..max[2] {
+                    for w in min[3]..max[3] {
+                        chunk.set([x, y, z, w], self.layer(w));
+                    }
+                }
+            }
+        }
+        chunk.build()
+    }
+
+    fn surface(&self, _column: [isize; 3]) -> Option<isize> {
+        self.layers
+            .iter()
+            .rposition(|layer| layer.info().opaque)
+            .map(|top| top as isize)
+    }
+
+    fn spawn_point(&self) -> [f32; 4] {
+        [0.5, 0.5, 0.5, self.layers.len() as f32 + 0.5]
+    }
+}
@@ -0,0 +1,178 @@
+use super::{ChunkBuilder, TerrainGenerator};
+use crate::block::BlockId;
+use crate::chunk::Chunk;
+use crate::noise::{Noise, Rng};
+
+/// A perfect maze through a 4D grid of cells, carved out of a block of stone, with air all around.
+///
+/// Cell `c` is the block `2 * c + 1`, and the blocks in between are walls or passages.
+/// The maze starts at cell `[0, 0, 0, 0]`, and the goal is the cell farthest from the start.
+pub struct Maze {
+    size: [usize; 4],
+    // For each cell, bit `i` is set if there is a passage to the next cell along axis `i`.
+    passages: Vec<u8>,
+    goal: [usize; 4],
+}
+
+// Keeps generation quick, and the passage list small.
+const MAX_CELLS: usize = 1 << 16;
+
+impl Maze {
+    /// Carve the maze with a randomized depth-first search.
+    pub fn new(seed: u64, size: [usize; 4]) -> Result<Self, String> {
+        let cells = size
+            .iter()
+            .try_fold(1_usize, |cells, &side| cells.checked_mul(side))
+            .filter(|cells| (1..=MAX_CELLS).contains(cells))
+            .ok_or_else(|| {
+                format!(
+                    "a maze of size {:?} has more than {} cells, or none",
+                    size, MAX_CELLS
+                )
+            })?;
+
+        let mut maze = Self {
+            size,
+            passages: vec![0; cells],
+            goal: [0; 4],
+        };
+        let mut rng = Rng::new(Noise::new(seed).hash(&[]));
+        let mut visited = vec![false; cells];
+        visited[0] = true;
+        let mut stack = vec![[0; 4]];
+        let mut goal_distance = 0;
+
+        while let Some(&cell) = stack.last() {
+            let mut unvisited = Vec::new();
+            for axis in 0..4 {
+                if cell[axis] > 0 {
+                    let mut neighbor = cell;
+                    neighbor[axis] -= 1;
+                    unvisited.push((axis, neighbor));
+                }
+                if cell[axis] + 1 < size[axis] {
+                    let mut neighbor = cell;
+                    neighbor[axis] += 1;
+                    unvisited.push((axis, neighbor));
+                }
+            }
+            unvisited.retain(|&(_, neighbor)| !visited[maze.index(neighbor)]);
+            if unvisited.is_empty() {
+                stack.pop();
+                continue;
+            }
+
+            let (axis, next) = unvisited[(rng.next_u64() % unvisited.len() as u64) as usize];
+            let lower = if next[axis] < cell[axis] { next } else { cell };
+            let lower = maze.index(lower);
+            maze.passages[lower] |= 1 << axis;
+            visited[maze.index(next)] = true;
+            stack.push(next);
+
+            // The stack holds the path from the start.
+            if stack.len() - 1 > goal_distance {
+                goal_distance = stack.len() - 1;
+                maze.goal = next;
+            }
+        }
+
+        Ok(maze)
+    }
+
+    fn index(&self, cell: [usize; 4]) -> usize {
+        let mut index = 0;
+        for i in (0..4).rev() {
+            index = index * self.size[i] + cell[i];
+        }
+        index
+    }
+
+    fn block(&self, coords: [isize; 4]) -> BlockId {
+        let mut cell = [0; 4];
+        // The axis along which `coords` lies between two cells, if any.
+        let mut between = None;
+        for i in 0..4 {
+            if !(0..=2 * self.size[i] as isize).contains(&coords[i]) {
+                return BlockId::AIR;
+            }
+            if coords[i] % 2 == 1 {
+                cell[i] = coords[i] as usize / 2;
+            } else if between.is_none() && 0 < coords[i] && coords[i] < 2 * self.size[i] as isize {
+                cell[i] = coords[i] as usize / 2 - 1;
+                between = Some(i);
+            } else {
+                return BlockId::STONE;
+            }
+        }
+        match between {
+            None => BlockId::AIR,
+            Some(axis) if self.passages[self.index(cell)] & 1 << axis != 0 => BlockId::AIR,
+            Some(_) => BlockId::STONE,
+        }
+    }
+}
+
+fn cell_center(cell: [usize; 4]) -> [f32; 4] {
+    let mut out = [0.0; 4];
+    for i in 0..4 {
+        out[i] = 2.0 * cell[i] as f32 + 1.5;
+    }
+    out
+}
+
+impl TerrainGenerator for Maze {
+    fn generate(&self, chunk_size: usize, which_chunk: [isize; 4]) -> Chunk {
+        let mut chunk = ChunkBuilder::new(chunk_size, which_chunk, BlockId::AIR);
+        let [min, max] = [chunk.min(), chunk.max()];
+        for x in min[0]..max[0] {
+            for y in min[1]..max[1] {
+                for z in min[2]..max[2] {
+                    for w in min[3]..max[3] {
+                        chunk.set([x, y, z, w], self.block([x, y, z, w]));
+                    }
+                }
+            }
+        }
+        chunk.build()
+    }
+
+    // The maze is a solid box with passages inside, so its top is the outer wall.
+    fn surface(&self, column: [isize; 3]) -> Option<isize> {
+        if (0..3).all(|i| (0..=2 * self.size[i] as isize).contains(&column[i])) {
+            Some(2 * self.size[3] as isize)
+        } else {
+            None
+        }
+    }
+
+    fn spawn_point(&self) -> [f32; 4] {
+        cell_center([0; 4])
+    }
+
+    fn describe(&self, position: [f32; 4]) -> Option<String> {
+        let goal = cell_center(self.goal);
+        let reached = (0..4).all(|i| (position[i] - goal[i]).abs() < 0.5);
+        Some(if reached {
+            "You have reached the goal!".to_owned()
+        } else {
+            format!("Start: {:?}\nGoal: {:?}", cell_center([0; 4]), goal)
+        })
+    }
+}
+
+/// Parses the maze size in cells: one number for every axis, or four comma-separated numbers.
+pub fn parse_size(s: &str) -> Result<[usize; 4], String> {
+    let sizes = s
+        .split(',')
+        .map(|size| {
+            size.trim()
+                .parse::<usize>()
+                .map_err(|_| format!("invalid maze size {:?}", size))
+        })
+        .collect::<Result<Vec<usize>, String>>()?;
+    match sizes[..] {
+        [n] => Ok([n; 4]),
+        [x, y, z, w] => Ok([x, y, z, w]),
+        _ => Err(format!("expected 1 or 4 maze sizes, found {}", sizes.len())),
+    }
+}
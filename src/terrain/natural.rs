@@ -0,0 +1,142 @@
+mod biomes;
+mod caves;
+mod ores;
+mod structures;
+
+use super::{ChunkBuilder, TerrainGenerator};
+use crate::block::BlockId;
+use crate::chunk::Chunk;
+use crate::noise::Noise;
+use biomes::{Biome, BIOMES};
+
+/// Rolling hills, in biomes chosen by climate.
+/// The height of the ground along `w` comes from noise over the `xyz` hyperplane.
+/// Caves and ore run underneath, and trees and huts stand on top.
+/// Wherever the ground is below sea level, water fills the space above it.
+pub struct Natural {
+    sea_level: isize,
+    height: Noise,
+    climate: biomes::Climate,
+    caves: caves::Caves,
+    ores: ores::Ores,
+    structures: structures::Structures,
+}
+
+// Rough width of a hill, in blocks.
+const HILL_SCALE: f64 = 48.0;
+
+pub const DEFAULT_SEA_LEVEL: isize = 0;
+// Ground this far from sea level, above or below, is beach.
+const SHORE_HEIGHT: isize = 2;
+
+/// What the ground is like at one point of the `xyz` hyperplane.
+struct Column {
+    biome: &'static Biome,
+    /// The `w` coordinate of the top block of the ground.
+    surface: isize,
+}
+
+impl Natural {
+    /// Water fills every cell with `w < sea_level` that is above the ground.
+    pub fn new(seed: u64, sea_level: isize) -> Self {
+        let noise = Noise::new(seed);
+        Self {
+            sea_level,
+            height: noise.derive(0),
+            caves: caves::Caves::new(noise.derive(1)),
+            climate: biomes::Climate::new(noise.derive(2)),
+            structures: structures::Structures::new(noise.derive(3), sea_level),
+            ores: ores::Ores::new(noise.derive(4)),
+        }
+    }
+
+    /// The biome is whichever contributes most to the column,
+    /// but the height blends between all of them, so that there are no cliffs at biome borders.
+    fn column(&self, [x, y, z]: [isize; 3]) -> Column {
+        let weights = self.climate.weights([x, y, z]);
+        let p = [
+            x as f64 / HILL_SCALE,
+            y as f64 / HILL_SCALE,
+            z as f64 / HILL_SCALE,
+        ];
+        let hills = self.height.fractal3(p, 4);
+
+        let mut height = 0.0;
+        let mut biome = &BIOMES[0];
+        let mut biome_weight = 0.0;
+        for (&weight, candidate) in weights.iter().zip(BIOMES) {
+            height += weight * (candidate.base_height + candidate.amplitude * hills);
+            if weight > biome_weight {
+                biome = candidate;
+                biome_weight = weight;
+            }
+        }
+        Column {
+            biome,
+            surface: height.floor() as isize,
+        }
+    }
+}
+
+impl TerrainGenerator for Natural {
+    fn generate(&self, chunk_size: usize, which_chunk: [isize; 4]) -> Chunk {
+        let mut chunk = ChunkBuilder::new(chunk_size, which_chunk, BlockId::AIR);
+        let [min, max] = [chunk.min(), chunk.max()];
+        for x in min[0]..max[0] {
+            for y in min[1]..max[1] {
+                for z in min[2]..max[2] {
+                    let Column { biome, surface } = self.column([x, y, z]);
+                    let (top, filler) = if surface < self.sea_level - SHORE_HEIGHT {
+                        (BlockId::GRAVEL, biome.filler)
+                    } else if surface <= self.sea_level + SHORE_HEIGHT {
+                        (BlockId::SAND, BlockId::SAND)
+                    } else {
+                        (biome.surface, biome.filler)
+                    };
+
+                    for w in min[3]..max[3].min(self.sea_level) {
+                        if w > surface {
+                            chunk.set([x, y, z, w], BlockId::WATER);
+                        }
+                    }
+                    for w in min[3]..max[3].min(surface + 1) {
+                        let block = if self.caves.is_cavern([x, y, z, w]) {
+                            BlockId::AIR
+                        } else if w == surface {
+                            top
+                        } else if w >= surface - biome.filler_depth {
+                            filler
+                        } else {
+                            self.ores.replace_stone([x, y, z, w])
+                        };
+                        chunk.set([x, y, z, w], block);
+                    }
+                }
+            }
+        }
+        self.caves
+            .carve_worms(&mut chunk, &|column| self.column(column).surface);
+        self.structures
+            .place(&mut chunk, &|column| self.column(column));
+        chunk.build()
+    }
+
+    // Trees and huts are left out, and caves that break through the ground are not noticed.
+    fn surface(&self, column: [isize; 3]) -> Option<isize> {
+        Some(self.column(column).surface)
+    }
+
+    fn spawn_point(&self) -> [f32; 4] {
+        let ground = self.column([0, 0, 0]).surface + 1;
+        [0.5, 0.5, 0.5, ground.max(self.sea_level) as f32 + 0.5]
+    }
+
+    fn describe(&self, position: [f32; 4]) -> Option<String> {
+        let column = [
+            position[0].floor() as isize,
+            position[1].floor() as isize,
+            position[2].floor() as isize,
+        ];
+        Some(format!("Biome: {}", self.column(column).biome.name))
+    }
+}
@@ -0,0 +1,123 @@
+use crate::block::BlockId;
+use crate::noise::Noise;
+
+/// A kind of landscape, chosen by the climate of each column.
+pub struct Biome {
+    pub name: &'static str,
+    /// The top block of the ground.
+    pub surface: BlockId,
+    /// What lies under the surface block, for `filler_depth` blocks, before the stone starts.
+    pub filler: BlockId,
+    pub filler_depth: isize,
+    /// The ground lies within `amplitude` of `base_height`.
+    pub base_height: f64,
+    pub amplitude: f64,
+    /// How many trees and other structures are built here, per column.
+    pub feature_density: f64,
+    // The climate this biome is found in. Both lie roughly in [-0.5, 0.5].
+    temperature: f64,
+    humidity: f64,
+}
+
+pub const BIOMES: &[Biome] = &[
+    Biome {
+        name: "plains",
+        surface: BlockId::GRASS,
+        filler: BlockId::DIRT,
+        filler_depth: 3,
+        base_height: 2.0,
+        amplitude: 4.0,
+        feature_density: 0.0003,
+        temperature: 0.0,
+        humidity: 0.0,
+    },
+    Biome {
+        name: "forest",
+        surface: BlockId::GRASS,
+        filler: BlockId::DIRT,
+        filler_depth: 4,
+        base_height: 4.0,
+        amplitude: 10.0,
+        feature_density: 0.0015,
+        temperature: 0.15,
+        humidity: 0.35,
+    },
+    Biome {
+        name: "desert",
+        surface: BlockId::SAND,
+        filler: BlockId::SAND,
+        filler_depth: 5,
+        base_height: 0.0,
+        amplitude: 3.0,
+        feature_density: 0.00008,
+        temperature: 0.4,
+        humidity: -0.3,
+    },
+    Biome {
+        name: "mountains",
+        surface: BlockId::STONE,
+        filler: BlockId::STONE,
+        filler_depth: 0,
+        base_height: 14.0,
+        amplitude: 24.0,
+        feature_density: 0.0001,
+        temperature: -0.15,
+        humidity: -0.3,
+    },
+    Biome {
+        name: "tundra",
+        surface: BlockId::SNOW,
+        filler: BlockId::DIRT,
+        filler_depth: 2,
+        base_height: 6.0,
+        amplitude: 8.0,
+        feature_density: 0.0004,
+        temperature: -0.4,
+        humidity: 0.2,
+    },
+];
+
+// Rough width of a climate zone, in blocks.
+const CLIMATE_SCALE: f64 = 256.0;
+// How far apart two climates can be while still mixing noticeably.
+// Smaller values make sharper borders between biomes.
+const BLEND: f64 = 0.12;
+
+/// Temperature and humidity, varying slowly over the `xyz` hyperplane.
+pub struct Climate {
+    temperature: Noise,
+    humidity: Noise,
+}
+
+impl Climate {
+    pub fn new(noise: Noise) -> Self {
+        Self {
+            temperature: noise.derive(0),
+            humidity: noise.derive(1),
+        }
+    }
+
+    /// How much each biome in [`BIOMES`] contributes to the column `[x, y, z]`.
+    /// The weights are positive and sum to one.
+    pub fn weights(&self, [x, y, z]: [isize; 3]) -> [f64; BIOMES.len()] {
+        let p = [
+            x as f64 / CLIMATE_SCALE,
+            y as f64 / CLIMATE_SCALE,
+            z as f64 / CLIMATE_SCALE,
+        ];
+        let temperature = self.temperature.fractal3(p, 2);
+        let humidity = self.humidity.fractal3(p, 2);
+
+        let mut weights = [0.0; BIOMES.len()];
+        for (weight, biome) in weights.iter_mut().zip(BIOMES) {
+            let distance_squared =
+                (temperature - biome.temperature).powi(2) + (humidity - biome.humidity).powi(2);
+            *weight = (-distance_squared / (BLEND * BLEND)).exp();
+        }
+        let total: f64 = weights.iter().sum();
+        for weight in weights.iter_mut() {
+            *weight /= total;
+        }
+        weights
+    }
+}
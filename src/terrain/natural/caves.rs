@@ -0,0 +1,127 @@
+use crate::block::BlockId;
+use crate::noise::{Noise, Rng};
+use crate::terrain::ChunkBuilder;
+
+/// Hollows out the ground.
+/// Caverns are wherever a 4D noise field is high, and worms are tunnels that wander through all four axes.
+pub struct Caves {
+    cavern: Noise,
+    worms: Noise,
+}
+
+// Rough width of a cavern, in blocks.
+const CAVERN_SCALE: f64 = 20.0;
+const CAVERN_THRESHOLD: f64 = 0.55;
+
+// Worms start on a grid of cells in the xyz hyperplane, at most one per cell, some way below the surface.
+const WORM_CELL: isize = 48;
+const WORM_CHANCE: f64 = 0.75;
+const WORM_DEPTH: (f64, f64) = (6.0, 30.0);
+const WORM_STEPS: usize = 60;
+const WORM_RADIUS: (f64, f64) = (1.2, 2.4);
+// How far a worm can get from its starting point, counting its radius.
+const WORM_REACH: isize = WORM_STEPS as isize + 3;
+
+impl Caves {
+    pub fn new(noise: Noise) -> Self {
+        Self {
+            cavern: noise.derive(0),
+            worms: noise.derive(1),
+        }
+    }
+
+    pub fn is_cavern(&self, [x, y, z, w]: [isize; 4]) -> bool {
+        let p = [
+            x as f64 / CAVERN_SCALE,
+            y as f64 / CAVERN_SCALE,
+            z as f64 / CAVERN_SCALE,
+            w as f64 / CAVERN_SCALE,
+        ];
+        self.cavern.noise4(p) > CAVERN_THRESHOLD
+    }
+
+    /// Carve every worm that passes through the chunk, wherever it started.
+    /// `surface` gives the height of the ground, as in [`super::Natural`].
+    pub fn carve_worms(&self, chunk: &mut ChunkBuilder, surface: &dyn Fn([isize; 3]) -> isize) {
+        let [min, max] = [chunk.min(), chunk.max()];
+        let cell_range = |i: usize| {
+            (min[i] - WORM_REACH).div_euclid(WORM_CELL)
+                ..=(max[i] + WORM_REACH).div_euclid(WORM_CELL)
+        };
+        for cx in cell_range(0) {
+            for cy in cell_range(1) {
+                for cz in cell_range(2) {
+                    self.carve_worm(chunk, [cx, cy, cz], surface);
+                }
+            }
+        }
+    }
+
+    fn carve_worm(
+        &self,
+        chunk: &mut ChunkBuilder,
+        cell: [isize; 3],
+        surface: &dyn Fn([isize; 3]) -> isize,
+    ) {
+        let [min, max] = [chunk.min(), chunk.max()];
+        let mut rng = Rng::new(
+            self.worms
+                .hash(&[cell[0] as i64, cell[1] as i64, cell[2] as i64]),
+        );
+        if rng.next_f64() >= WORM_CHANCE {
+            return;
+        }
+
+        let mut column = [0; 3];
+        for i in 0..3 {
+            column[i] = cell[i] * WORM_CELL + (rng.next_f64() * WORM_CELL as f64) as isize;
+        }
+        let depth = rng.range(WORM_DEPTH.0, WORM_DEPTH.1);
+        let mut position = [
+            column[0] as f64 + 0.5,
+            column[1] as f64 + 0.5,
+            column[2] as f64 + 0.5,
+            surface(column) as f64 - depth,
+        ];
+        let mut direction = [0.0; 4];
+        let radius = rng.range(WORM_RADIUS.0, WORM_RADIUS.1);
+
+        for _ in 0..WORM_STEPS {
+            // Turn a little, then take a step of length one.
+            for d in direction.iter_mut() {
+                *d += rng.range(-0.5, 0.5);
+            }
+            let length = direction.iter().map(|d| d * d).sum::<f64>().sqrt();
+            for i in 0..4 {
+                direction[i] /= length;
+                position[i] += direction[i];
+            }
+
+            // Carve a ball around the worm's position, clipped to the chunk.
+            let mut ball_min = [0; 4];
+            let mut ball_max = [0; 4];
+            for i in 0..4 {
+                ball_min[i] = ((position[i] - radius).floor() as isize).max(min[i]);
+                ball_max[i] = ((position[i] + radius).ceil() as isize).min(max[i]);
+            }
+            for x in ball_min[0]..ball_max[0] {
+                for y in ball_min[1]..ball_max[1] {
+                    for z in ball_min[2]..ball_max[2] {
+                        for w in ball_min[3]..ball_max[3] {
+                            let b = [x, y, z, w];
+                            let distance_squared: f64 = (0..4)
+                                .map(|i| (b[i] as f64 + 0.5 - position[i]).powi(2))
+                                .sum();
+                            // Keep the sea out of the tunnels.
+                            if distance_squared <= radius * radius
+                                && chunk.get(b) != Some(BlockId::WATER)
+                            {
+                                chunk.set(b, BlockId::AIR);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
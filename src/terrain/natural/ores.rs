@@ -0,0 +1,80 @@
+use crate::block::BlockId;
+use crate::noise::Noise;
+
+/// Spreads ore through the stone, in blobs wherever a 4D noise field is high enough.
+pub struct Ores {
+    veins: Vec<Noise>,
+}
+
+struct Ore {
+    block: BlockId,
+    // Rough width of a vein, in blocks.
+    scale: f64,
+    // Veins are commonest at `w = depth`, and thin out to nothing `spread` blocks above or below.
+    depth: f64,
+    spread: f64,
+    // How high the noise must be for ore, at the best depth. Higher thresholds make rarer ore.
+    threshold: f64,
+}
+
+// Earlier ores take precedence where veins overlap.
+const ORES: &[Ore] = &[
+    Ore {
+        block: BlockId::DIAMOND,
+        scale: 1.5,
+        depth: -80.0,
+        spread: 24.0,
+        threshold: 0.9,
+    },
+    Ore {
+        block: BlockId::GOLD,
+        scale: 2.0,
+        depth: -50.0,
+        spread: 30.0,
+        threshold: 0.85,
+    },
+    Ore {
+        block: BlockId::IRON,
+        scale: 2.5,
+        depth: -20.0,
+        spread: 40.0,
+        threshold: 0.75,
+    },
+    Ore {
+        block: BlockId::COAL,
+        scale: 3.0,
+        depth: 0.0,
+        spread: 50.0,
+        threshold: 0.7,
+    },
+];
+
+impl Ores {
+    pub fn new(noise: Noise) -> Self {
+        Self {
+            veins: (0..ORES.len() as u64).map(|i| noise.derive(i)).collect(),
+        }
+    }
+
+    /// What a stone block at `coords` should be instead, if anything.
+    pub fn replace_stone(&self, [x, y, z, w]: [isize; 4]) -> BlockId {
+        for (ore, noise) in ORES.iter().zip(&self.veins) {
+            let distance = (w as f64 - ore.depth).abs() / ore.spread;
+            if distance >= 1.0 {
+                continue;
+            }
+            // Raise the threshold away from the best depth, until nothing exceeds it.
+            let threshold = ore.threshold + (1.0 - ore.threshold) * distance * distance;
+            let p = [
+                x as f64 / ore.scale,
+                y as f64 / ore.scale,
+                z as f64 / ore.scale,
+                w as f64 / ore.scale,
+            ];
+            if noise.noise4(p) > threshold {
+                return ore.block;
+            }
+        }
+        BlockId::STONE
+    }
+}
@@ -0,0 +1,158 @@
+use super::Column;
+use crate::block::BlockId;
+use crate::noise::{Noise, Rng};
+use crate::terrain::ChunkBuilder;
+
+/// Builds trees and huts on dry ground.
+///
+/// Each cell of a grid over the `xyz` hyperplane holds at most one structure, anchored somewhere in the cell.
+/// Everything about a structure comes from its cell's hash, so every chunk it overlaps draws the same structure.
+pub struct Structures {
+    noise: Noise,
+    sea_level: isize,
+}
+
+// The width of a cell, in blocks.
+const CELL: isize = 8;
+// How far a structure can extend from its anchor, horizontally,
+// and how far it can extend above the top block of the ground, which it may replace.
+const REACH: isize = 4;
+const HEIGHT: isize = 12;
+
+const HUT_CHANCE: f64 = 0.2;
+const HUT_SIZE: isize = 5;
+
+impl Structures {
+    pub fn new(noise: Noise, sea_level: isize) -> Self {
+        Self { noise, sea_level }
+    }
+
+    /// Draw every structure that overlaps the chunk, wherever it is anchored.
+    pub fn place(&self, chunk: &mut ChunkBuilder, column: &dyn Fn([isize; 3]) -> Column) {
+        let [min, max] = [chunk.min(), chunk.max()];
+        let cell_range =
+            |i: usize| (min[i] - REACH).div_euclid(CELL)..=(max[i] + REACH).div_euclid(CELL);
+        for cx in cell_range(0) {
+            for cy in cell_range(1) {
+                for cz in cell_range(2) {
+                    self.place_in_cell(chunk, [cx, cy, cz], column);
+                }
+            }
+        }
+    }
+
+    fn place_in_cell(
+        &self,
+        chunk: &mut ChunkBuilder,
+        cell: [isize; 3],
+        column: &dyn Fn([isize; 3]) -> Column,
+    ) {
+        let [min, max] = [chunk.min(), chunk.max()];
+        let mut rng = Rng::new(
+            self.noise
+                .hash(&[cell[0] as i64, cell[1] as i64, cell[2] as i64]),
+        );
+
+        let mut anchor = [0; 3];
+        for i in 0..3 {
+            anchor[i] = cell[i] * CELL + (rng.next_f64() * CELL as f64) as isize;
+            if anchor[i] + REACH < min[i] || anchor[i] - REACH >= max[i] {
+                return;
+            }
+        }
+
+        let Column { biome, surface } = column(anchor);
+        if rng.next_f64() >= biome.feature_density * CELL.pow(3) as f64 {
+            return;
+        }
+        if surface < self.sea_level || surface + HEIGHT < min[3] || surface >= max[3] {
+            return;
+        }
+
+        let base = [anchor[0], anchor[1], anchor[2], surface + 1];
+        if rng.next_f64() < HUT_CHANCE {
+            hut(chunk, base);
+        } else {
+            hypertree(chunk, base, &mut rng);
+        }
+    }
+}
+
+/// A trunk along `w`, with a branch along each horizontal direction and leaves around the top.
+fn hypertree(chunk: &mut ChunkBuilder, base: [isize; 4], rng: &mut Rng) {
+    let height = rng.range(4.0, 8.0) as isize;
+    let top = [base[0], base[1], base[2], base[3] + height];
+
+    // Branches stick out of the trunk two blocks below the top.
+    let mut tips = vec![top];
+    for axis in 0..3 {
+        for &direction in &[-1, 1] {
+            let mut branch = top;
+            branch[3] -= 2;
+            for _ in 0..2 {
+                branch[axis] += direction;
+                chunk.set(branch, BlockId::WOOD);
+            }
+            tips.push(branch);
+        }
+    }
+    for (i, &tip) in tips.iter().enumerate() {
+        let radius = if i == 0 { 2 } else { 1 };
+        leaves(chunk, tip, radius);
+    }
+
+    for w in base[3]..top[3] {
+        chunk.set([base[0], base[1], base[2], w], BlockId::WOOD);
+    }
+}
+
+/// Fill air with leaves, within a radius of `center`.
+fn leaves(chunk: &mut ChunkBuilder, center: [isize; 4], radius: isize) {
+    for x in -radius..=radius {
+        for y in -radius..=radius {
+            for z in -radius..=radius {
+                for w in -radius..=radius {
+                    if x * x + y * y + z * z + w * w > radius * radius + 1 {
+                        continue;
+                    }
+                    let b = [center[0] + x, center[1] + y, center[2] + z, center[3] + w];
+                    if chunk.get(b) == Some(BlockId::AIR) {
+                        chunk.set(b, BlockId::LEAVES);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A hollow tesseract of planks, with a doorway on its `-x` face.
+fn hut(chunk: &mut ChunkBuilder, base: [isize; 4]) {
+    // Center the hut on the anchor horizontally, with its floor on the ground.
+    let corner = [
+        base[0] - HUT_SIZE / 2,
+        base[1] - HUT_SIZE / 2,
+        base[2] - HUT_SIZE / 2,
+        base[3] - 1,
+    ];
+    for x in 0..HUT_SIZE {
+        for y in 0..HUT_SIZE {
+            for z in 0..HUT_SIZE {
+                for w in 0..HUT_SIZE {
+                    let rel = [x, y, z, w];
+                    let on_wall = rel.iter().any(|&c| c == 0 || c == HUT_SIZE - 1);
+                    let doorway =
+                        x == 0 && y == HUT_SIZE / 2 && z == HUT_SIZE / 2 && (1..3).contains(&w);
+                    let block = if on_wall && !doorway {
+                        BlockId::PLANKS
+                    } else {
+                        BlockId::AIR
+                    };
+                    chunk.set(
+                        [corner[0] + x, corner[1] + y, corner[2] + z, corner[3] + w],
+                        block,
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,262 @@
+//! Golden hashes of generated chunks.
+//!
+//! If a change to generation is intended, update the hashes from the failure message.
+//! Otherwise, every existing world would change under its players.
+
+use super::{create, TerrainGenerator, GENERATORS};
+use crate::chunk::Chunk;
+use crate::config::Config;
+use crate::world::{Budget, Topology, World};
+
+use std::collections::HashMap;
+
+const CHUNK_SIZE: usize = 8;
+
+const CHUNKS: &[[isize; 4]] = &[
+    [0, 0, 0, 0],
+    [0, 0, 0, -1],
+    [0, 0, 0, 1],
+    [-1, 2, 0, -1],
+    [3, -2, 1, 0],
+    [1, 1, 1, -4],
+    [-5, 0, 7, -10],
+];
+
+// A generator, the parameters it is created with, and the hash of each of `CHUNKS`.
+type Case = (
+    &'static str,
+    &'static [(&'static str, &'static str)],
+    [u64; 7],
+);
+
+const GOLDEN: &[Case] = &[
+    (
+        "classic",
+        &[],
+        [
+            0xf9be8dcd980a7304,
+            0xd26914f649579858,
+            0xb93a0c83ce3b6325,
+            0xb93a0c83ce3b6325,
+            0xb93a0c83ce3b6325,
+            0xb93a0c83ce3b6325,
+            0xb93a0c83ce3b6325,
+        ],
+    ),
+    (
+        "natural",
+        &[("seed", "0")],
+        [
+            0xe03ac6d5af9e0e7b,
+            0x3df8658389d2f0c2,
+            0xb93a0c83ce3b6325,
+            0x359a99844ce27034,
+            0xa782580a080aae71,
+            0x2de3d483123610b8,
+            0xf19ee69ee2dcc5ce,
+        ],
+    ),
+    (
+        "natural",
+        &[("seed", "12345")],
+        [
+            0x2254cc3308639a5b,
+            0x69a1287d4c4b5cb,
+            0xb93a0c83ce3b6325,
+            0xb4cd02a84c8f9362,
+            0x4442d64a3b99b8c,
+            0x73e5b6a30233371,
+            0xfdf11feed9eb78,
+        ],
+    ),
+    (
+        "natural",
+        &[("seed", "3"), ("sea_level", "8")],
+        [
+            0x439879bd597037d,
+            0x17bc8216d36fc1f2,
+            0xb93a0c83ce3b6325,
+            0xd4245dd4a87a6140,
+            0xa2e1386dc59faaa5,
+            0xdc35cd771bc3d98,
+            0x8821957d497ff6b5,
+        ],
+    ),
+    (
+        "maze",
+        &[("seed", "0")],
+        [
+            0xce6c45ca864e663c,
+            0x2777dbe76de9e525,
+            0x26ffa0de258056e5,
+            0xb93a0c83ce3b6325,
+            0xb93a0c83ce3b6325,
+            0x70a6ee0ac1b14325,
+            0xb93a0c83ce3b6325,
+        ],
+    ),
+    (
+        "maze",
+        &[("seed", "7"), ("size", "3,2,4,2")],
+        [
+            0x9b89a3ab496c7748,
+            0x6cc63a7134aec325,
+            0x6cc63a7134aec325,
+            0xb93a0c83ce3b6325,
+            0xb93a0c83ce3b6325,
+            0xb93a0c83ce3b6325,
+            0xb93a0c83ce3b6325,
+        ],
+    ),
+    (
+        "flat",
+        &[],
+        [
+            0x7c2c37f76d271525,
+            0x13411b19e5157325,
+            0xb93a0c83ce3b6325,
+            0x13411b19e5157325,
+            0x7c2c37f76d271525,
+            0x13411b19e5157325,
+            0x13411b19e5157325,
+        ],
+    ),
+    (
+        "flat",
+        &[("layers", "barrier,stone*3,sand*2")],
+        [
+            0x43dea071bea59f25,
+            0x864caa25d37cf325,
+            0xb93a0c83ce3b6325,
+            0x864caa25d37cf325,
+            0x43dea071bea59f25,
+            0x864caa25d37cf325,
+            0x864caa25d37cf325,
+        ],
+    ),
+];
+
+fn generator(name: &str, params: &[(&str, &str)]) -> Box<dyn TerrainGenerator> {
+    let params = |key: &str| {
+        params
+            .iter()
+            .find(|&&(k, _)| k == key)
+            .map(|&(_, v)| v.to_owned())
+    };
+    create(name, &params).unwrap()
+}
+
+/// FNV-1a over the chunk's blocks.
+fn hash(chunk: &Chunk) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325;
+    chunk.pass_as_slice(|blocks| {
+        for &b in blocks {
+            hash ^= b as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    });
+    hash
+}
+
+#[test]
+fn golden_hashes() {
+    let mut failures = Vec::new();
+    for (name, params, expected) in GOLDEN {
+        let generator = generator(name, params);
+        let actual: Vec<u64> = CHUNKS
+            .iter()
+            .map(|&which| hash(&generator.generate(CHUNK_SIZE, which)))
+            .collect();
+        if actual[..] != expected[..] {
+            failures.push(format!("({:?}, &{:?}, {:#x?}),", name, params, actual));
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "generation changed:\n{}",
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn every_generator_is_tested() {
+    for entry in GENERATORS {
+        assert!(
+            GOLDEN.iter().any(|&(name, _, _)| name == entry.name),
+            "no golden hashes for {:?}",
+            entry.name
+        );
+    }
+}
+
+/// Loading chunks in different orders, and evicting and regenerating them, must not change what they contain.
+#[test]
+fn generation_order_does_not_matter() {
+    for (name, params, _) in GOLDEN {
+        let mut hashes = Vec::new();
+        for &(reverse, budget) in &[(false, Budget::Unlimited), (true, Budget::Chunks(1))] {
+            let mut world = World::new(
+                Config::new(CHUNK_SIZE, 4),
+                Topology::UNBOUNDED,
+                generator(name, params),
+                budget,
+                Box::new(HashMap::new()),
+            );
+            let mut order = CHUNKS.to_vec();
+            if reverse {
+                order.reverse();
+            }
+            for &which in &order {
+                world.load_chunk(which);
+                world.evict();
+            }
+            for &which in CHUNKS {
+                world.load_chunk(which);
+            }
+            hashes.push(
+                CHUNKS
+                    .iter()
+                    .map(|&which| hash(world.chunk(which).unwrap()))
+                    .collect::<Vec<_>>(),
+            );
+        }
+        assert_eq!(hashes[0], hashes[1], "{} {:?}", name, params);
+    }
+}
+
+/// Features that cross chunk boundaries must be drawn the same in every chunk they touch.
+/// A chunk twice the size covers 16 ordinary chunks, and must agree with all of them.
+#[test]
+fn chunk_size_does_not_matter() {
+    for (name, params, _) in GOLDEN {
+        let generator = generator(name, params);
+        for &big_chunk in &[[0, 0, 0, 0], [-1, 0, 1, -1]] {
+            let big = generator.generate(2 * CHUNK_SIZE, big_chunk);
+            for i in 0..16 {
+                let mut which = [0; 4];
+                for (axis, c) in which.iter_mut().enumerate() {
+                    *c = 2 * big_chunk[axis] + (i >> axis & 1);
+                }
+                let small = generator.generate(CHUNK_SIZE, which);
+                let size = CHUNK_SIZE as isize;
+                for rel in 0..size.pow(4) {
+                    let rel = [
+                        rel % size,
+                        rel / size % size,
+                        rel / size.pow(2) % size,
+                        rel / size.pow(3),
+                    ];
+                    let mut big_rel = rel;
+                    for axis in 0..4 {
+                        big_rel[axis] += size * (which[axis] - 2 * big_chunk[axis]);
+                    }
+                    assert_eq!(
+                        small[rel], big[big_rel],
+                        "{} {:?} at {:?} in {:?}",
+                        name, params, rel, which
+                    );
+                }
+            }
+        }
+    }
+}
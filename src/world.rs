@@ -1,45 +1,415 @@
-use crate::block::{Block, BlockName};
+mod block_box;
+mod cursor;
+mod fluid;
+mod heightmap;
+mod light;
+mod topology;
+
+use crate::block::{Block, BlockId, BlockState};
 use crate::chunk::Chunk;
+use crate::config::Config;
+use crate::terrain::TerrainGenerator;
+use heightmap::Column;
+
+pub use block_box::BlockBox;
+pub use cursor::Cursor;
+pub use light::Light;
+pub use topology::{Boundary, Topology};
 
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
+/// Chunks are only ever generated by the loading methods and by edits.
+/// Every other query leaves the world untouched, reporting ungenerated chunks as `None`.
+///
+/// Methods accept coordinates outside a wrapping world, and treat them as the coordinates they wrap around to.
 pub struct World {
-    chunks: RefCell<HashMap<[isize; 4], Chunk>>,
+    config: Config,
+    topology: Topology,
+    generator: Box<dyn TerrainGenerator>,
+    // Keyed by canonical chunk coordinates.
+    chunks: HashMap<[isize; 4], LoadedChunk>,
+    // Stands in for every chunk beyond a wall.
+    outside: Chunk,
+    // Keyed by canonical chunk coordinates without `w`. A column is kept while any chunk in it is loaded, or was modified.
+    columns: HashMap<[isize; 3], Column>,
+
+    budget: Budget,
+    store: Box<dyn ChunkStore>,
+    // Incremented by every call to `evict`.
+    clock: u64,
+
+    changes: Changes,
+
+    // Incremented by every call to `tick`.
+    tick: u64,
+    // The cells waiting for a fluid update, by the tick they are due.
+    fluid_updates: BTreeMap<u64, Vec<[isize; 4]>>,
+    // Every cell in `fluid_updates`.
+    scheduled: HashSet<[isize; 4]>,
+}
+
+// The world is shared by reference rather than through interior mutability, so that it can be shared between threads.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<World>();
+};
+
+/// Everything edited since the last call to `World::drain_changes`, in canonical coordinates.
+/// Whoever drains the changes must pass them on to everything that mirrors the world, such as the renderer.
+#[derive(Default)]
+pub struct Changes {
+    pub blocks: HashSet<[isize; 4]>,
+    pub chunks: HashSet<[isize; 4]>,
+    /// Cells whose light level changed, by the chunk they are in, other than in chunks that were loaded since the last drain.
+    pub light: HashMap<[isize; 4], HashSet<[isize; 4]>>,
+}
+
+struct LoadedChunk {
+    chunk: Chunk,
+    light: Light,
+    // The value of `World::clock` when this chunk was last loaded.
+    last_used: u64,
+    // Whether the chunk differs from what the generator would produce.
+    modified: bool,
+}
+
+/// How much memory the loaded chunks may use before `World::evict` starts dropping them.
+#[allow(dead_code)] // The page only uses one kind of budget.
+#[derive(Debug, Copy, Clone)]
+pub enum Budget {
+    Unlimited,
+    Chunks(usize),
+    Bytes(usize),
+}
+
+/// Where modified chunks go when they are evicted, so that edits are never lost.
+pub trait ChunkStore: Send + Sync {
+    fn save(&mut self, which_chunk: [isize; 4], chunk: Chunk);
+    /// Remove a previously saved chunk from the store.
+    fn take(&mut self, which_chunk: [isize; 4]) -> Option<Chunk>;
+}
+
+/// Keeps evicted chunks in memory, exactly as they were loaded.
+/// Only their light is dropped, to be worked out again when they are next loaded.
+impl ChunkStore for HashMap<[isize; 4], Chunk> {
+    fn save(&mut self, which_chunk: [isize; 4], chunk: Chunk) {
+        self.insert(which_chunk, chunk);
+    }
+    fn take(&mut self, which_chunk: [isize; 4]) -> Option<Chunk> {
+        self.remove(&which_chunk)
+    }
 }
 
 impl World {
-    pub fn new() -> Self {
+    pub fn new(
+        config: Config,
+        topology: Topology,
+        generator: Box<dyn TerrainGenerator>,
+        budget: Budget,
+        store: Box<dyn ChunkStore>,
+    ) -> Self {
         Self {
-            chunks: RefCell::new(HashMap::new()),
+            config,
+            topology,
+            generator,
+            chunks: HashMap::new(),
+            outside: Chunk::filled(config.chunk_size, BlockId::BARRIER),
+            columns: HashMap::new(),
+
+            budget,
+            store,
+            clock: 0,
+
+            changes: Changes::default(),
+
+            tick: 0,
+            fluid_updates: BTreeMap::new(),
+            scheduled: HashSet::new(),
         }
     }
 
-    pub fn get(&self, coords: [isize; 4]) -> BlockName {
-        let (which_chunk, rel_pos) = crate::chunk::chunk_position(coords);
+    /// Generate the chunk, if it has not been generated already.
+    /// Either way, the chunk counts as recently used.
+    pub fn load_chunk(&mut self, which_chunk: [isize; 4]) {
+        if let Some(which_chunk) = self.topology.canonical_chunk(which_chunk) {
+            self.load_canonical_chunk(which_chunk);
+        }
+    }
 
-        *self
-            .chunks
-            .borrow_mut()
-            .entry(which_chunk)
-            .or_insert_with(|| Chunk::new(which_chunk))[rel_pos]
+    fn load_canonical_chunk(&mut self, which_chunk: [isize; 4]) -> &mut LoadedChunk {
+        if !self.chunks.contains_key(&which_chunk) {
+            let (chunk, modified) = match self.store.take(which_chunk) {
+                Some(chunk) => (chunk, true),
+                None => (
+                    self.generator.generate(self.config.chunk_size, which_chunk),
+                    false,
+                ),
+            };
+            self.chunks.insert(
+                which_chunk,
+                LoadedChunk {
+                    chunk,
+                    light: Light::Uniform(0),
+                    last_used: 0,
+                    modified,
+                },
+            );
+            self.light_new_chunk(which_chunk);
+            self.schedule_chunk_faces(which_chunk);
+        }
+
+        let loaded = self.chunks.get_mut(&which_chunk).unwrap();
+        loaded.last_used = self.clock;
+        loaded
     }
 
-    pub fn get_mut(&mut self, coords: [isize; 4]) -> &mut Block {
-        let (which_chunk, rel_pos) = crate::chunk::chunk_position(coords);
+    /// Generate every chunk `c` with `min[i] <= c[i] < max[i]`.
+    /// Higher chunks come first, so that the sky is known to be blocked before the chunks under it are lit.
+    pub fn load_chunks(&mut self, min: [isize; 4], max: [isize; 4]) {
+        for w in (min[3]..max[3]).rev() {
+            for x in min[0]..max[0] {
+                for y in min[1]..max[1] {
+                    for z in min[2]..max[2] {
+                        self.load_chunk([x, y, z, w]);
+                    }
+                }
+            }
+        }
+    }
 
-        &mut self
+    /// Drop least recently used chunks until the world fits in its budget.
+    /// Chunks loaded since the previous call are never dropped.
+    /// Unmodified chunks will simply be generated again; modified ones are handed to the chunk store.
+    pub fn evict(&mut self) {
+        let mut usage = self.memory_usage();
+        if !self.over_budget(usage) {
+            self.clock += 1;
+            return;
+        }
+
+        let mut candidates: Vec<([isize; 4], u64)> = self
             .chunks
-            .get_mut()
-            .entry(which_chunk)
-            .or_insert_with(|| Chunk::new(which_chunk))[rel_pos]
+            .iter()
+            .filter(|(_, loaded)| loaded.last_used < self.clock)
+            .map(|(&which_chunk, loaded)| (which_chunk, loaded.last_used))
+            .collect();
+        candidates.sort_by_key(|&(_, last_used)| last_used);
+
+        for (which_chunk, _) in candidates {
+            if !self.over_budget(usage) {
+                break;
+            }
+            let loaded = self.chunks.remove(&which_chunk).unwrap();
+            usage -= self.cost(&loaded);
+            let column = [which_chunk[0], which_chunk[1], which_chunk[2]];
+            let before = self.column_cost(&self.columns[&column]);
+            if self
+                .columns
+                .get_mut(&column)
+                .unwrap()
+                .evict(which_chunk[3], loaded.modified)
+            {
+                self.columns.remove(&column);
+                usage -= before;
+            } else {
+                usage = usage - before + self.column_cost(&self.columns[&column]);
+            }
+            if loaded.modified {
+                self.store.save(which_chunk, loaded.chunk);
+            }
+        }
+
+        self.clock += 1;
     }
 
-    pub fn pass_chunk_as_slice(&self, which_chunk: [isize; 4], f: impl FnOnce(&[u8])) {
+    fn memory_usage(&self) -> usize {
         self.chunks
-            .borrow_mut()
-            .entry(which_chunk)
-            .or_insert_with(|| Chunk::new(which_chunk))
-            .pass_as_slice(f)
+            .values()
+            .map(|loaded| self.cost(loaded))
+            .chain(self.columns.values().map(|column| self.column_cost(column)))
+            .sum()
+    }
+
+    // How much of the budget a chunk takes up.
+    fn cost(&self, loaded: &LoadedChunk) -> usize {
+        match self.budget {
+            Budget::Unlimited => 0,
+            Budget::Chunks(_) => 1,
+            Budget::Bytes(_) => loaded.chunk.memory_usage() + loaded.light.memory_usage(),
+        }
+    }
+
+    // Columns only count towards a budget in bytes, as they come and go with their chunks.
+    fn column_cost(&self, column: &Column) -> usize {
+        match self.budget {
+            Budget::Bytes(_) => column.memory_usage(),
+            _ => 0,
+        }
+    }
+
+    fn over_budget(&self, usage: usize) -> bool {
+        match self.budget {
+            Budget::Unlimited => false,
+            Budget::Chunks(max) => usage > max,
+            Budget::Bytes(max) => usage > max,
+        }
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    pub fn spawn_point(&self) -> [f32; 4] {
+        self.wrap_position(self.generator.spawn_point())
+    }
+
+    pub fn describe(&self, position: [f32; 4]) -> Option<String> {
+        self.generator.describe(position)
+    }
+
+    /// Chunks beyond a wall are made of barrier blocks.
+    pub fn chunk(&self, which_chunk: [isize; 4]) -> Option<&Chunk> {
+        match self.topology.canonical_chunk(which_chunk) {
+            Some(which_chunk) => self.chunks.get(&which_chunk).map(|loaded| &loaded.chunk),
+            None => Some(&self.outside),
+        }
+    }
+
+    /// Move a position along each wrapping axis, until it is in the chunks `0..n`.
+    pub fn wrap_position(&self, mut pos: [f32; 4]) -> [f32; 4] {
+        for (x, boundary) in pos.iter_mut().zip(self.topology.0.iter()) {
+            if let Boundary::Wrapping(n) = *boundary {
+                *x = x.rem_euclid((n * self.config.chunk_size) as f32);
+            }
+        }
+        pos
+    }
+
+    // Where `coords` wraps around to, or `None` if it is beyond a wall.
+    fn canonical_coords(&self, coords: [isize; 4]) -> Option<[isize; 4]> {
+        let (which_chunk, rel_pos) = self.config.chunk_position(coords);
+        let which_chunk = self.topology.canonical_chunk(which_chunk)?;
+        let mut out = [0; 4];
+        for i in 0..4 {
+            out[i] = which_chunk[i] * self.config.chunk_size as isize + rel_pos[i];
+        }
+        Some(out)
+    }
+
+    /// Returns `None` if the block's chunk has not been generated yet.
+    pub fn get(&self, coords: [isize; 4]) -> Option<BlockId> {
+        let (which_chunk, rel_pos) = self.config.chunk_position(coords);
+        self.chunk(which_chunk).map(|chunk| chunk[rel_pos])
+    }
+
+    pub fn cursor(&self, coords: [isize; 4]) -> Cursor<'_> {
+        Cursor::new(self, coords)
+    }
+
+    /// Copy every block `b` with `min[i] <= b[i] < max[i]`, one chunk at a time.
+    /// Blocks in ungenerated chunks are read as `missing`.
+    pub fn read_box(&self, min: [isize; 4], max: [isize; 4], missing: BlockId) -> BlockBox {
+        let mut out = BlockBox::filled(min, max, missing);
+
+        let (min_chunk, _) = self.config.chunk_position(min);
+        let (max_chunk, _) =
+            self.config
+                .chunk_position([max[0] - 1, max[1] - 1, max[2] - 1, max[3] - 1]);
+
+        for x in min_chunk[0]..=max_chunk[0] {
+            for y in min_chunk[1]..=max_chunk[1] {
+                for z in min_chunk[2]..=max_chunk[2] {
+                    for w in min_chunk[3]..=max_chunk[3] {
+                        let which_chunk = [x, y, z, w];
+                        let chunk = match self.chunk(which_chunk) {
+                            Some(chunk) => chunk,
+                            None => continue,
+                        };
+
+                        // The part of the box inside this chunk, relative to the chunk.
+                        let mut rel_min = [0; 4];
+                        let mut rel_max = [0; 4];
+                        let mut chunk_corner = [0; 4];
+                        for i in 0..4 {
+                            chunk_corner[i] = which_chunk[i] * self.config.chunk_size as isize;
+                            rel_min[i] = (min[i] - chunk_corner[i]).max(0) as usize;
+                            rel_max[i] = (max[i] - chunk_corner[i])
+                                .min(self.config.chunk_size as isize)
+                                as usize;
+                        }
+
+                        chunk.for_each_in(rel_min, rel_max, |rel_pos, block| {
+                            out.set(
+                                [
+                                    chunk_corner[0] + rel_pos[0] as isize,
+                                    chunk_corner[1] + rel_pos[1] as isize,
+                                    chunk_corner[2] + rel_pos[2] as isize,
+                                    chunk_corner[3] + rel_pos[3] as isize,
+                                ],
+                                block,
+                            )
+                        });
+                    }
+                }
+            }
+        }
+
+        out
+    }
+
+    /// Returns `None` if the block's chunk has not been generated yet.
+    pub fn get_state(&self, coords: [isize; 4]) -> Option<BlockState> {
+        let (which_chunk, rel_pos) = self.config.chunk_position(coords);
+        self.chunk(which_chunk).map(|chunk| chunk.state(rel_pos))
+    }
+
+    /// Change the state of the block at `coords`, leaving the block itself in place.
+    pub fn set_state(&mut self, coords: [isize; 4], state: BlockState) {
+        let (which_chunk, rel_pos) = self.config.chunk_position(coords);
+        if let Some(which_chunk) = self.topology.canonical_chunk(which_chunk) {
+            let name = self.load_canonical_chunk(which_chunk).chunk[rel_pos];
+            // The block put in is the block taken out, so nothing is destroyed.
+            drop(self.replace(coords, Block::with_state(name, state)));
+        }
+    }
+
+    /// Put `block` at `coords`, returning the block that was there before.
+    /// Beyond a wall, nothing can be placed, so `block` itself is returned.
+    pub fn replace(&mut self, coords: [isize; 4], block: Block) -> Block {
+        let coords = match self.canonical_coords(coords) {
+            Some(coords) => coords,
+            None => return block,
+        };
+        let (which_chunk, rel_pos) = self.config.chunk_position(coords);
+
+        self.load_canonical_chunk(which_chunk);
+        let old_sky = self.sky_top(coords);
+        let loaded = self.chunks.get_mut(&which_chunk).unwrap();
+        let old = loaded.chunk.replace(rel_pos, block);
+        let new = loaded.chunk[rel_pos];
+        if new != *old {
+            let column = [which_chunk[0], which_chunk[1], which_chunk[2]];
+            if let Some(heightmap) = self
+                .columns
+                .get_mut(&column)
+                .and_then(|column| column.heightmap_mut(which_chunk[3]))
+            {
+                heightmap.update(&loaded.chunk, rel_pos);
+            }
+        }
+        if new != *old || loaded.chunk.state(rel_pos) != old.state() {
+            loaded.modified = true;
+            self.changes.blocks.insert(coords);
+            self.changes.chunks.insert(which_chunk);
+            self.schedule_fluid(coords, *old, new);
+        }
+        if new != *old {
+            self.update_light(coords, old_sky);
+        }
+        old
+    }
+
+    pub fn drain_changes(&mut self) -> Changes {
+        std::mem::take(&mut self.changes)
     }
 }
@@ -0,0 +1,55 @@
+use crate::block::BlockId;
+
+/// A dense copy of the blocks `b` with `min[i] <= b[i] < max[i]`, made by `World::read_box`.
+/// Reading from it involves no hashing, so it suits code that scans neighborhoods, like meshing.
+pub struct BlockBox {
+    min: [isize; 4],
+    max: [isize; 4],
+    blocks: Vec<BlockId>,
+}
+
+impl BlockBox {
+    pub(super) fn filled(min: [isize; 4], max: [isize; 4], block: BlockId) -> Self {
+        let mut volume = 1;
+        for i in 0..4 {
+            volume *= (max[i] - min[i]).max(0) as usize;
+        }
+        Self {
+            min,
+            max,
+            blocks: vec![block; volume],
+        }
+    }
+
+    pub fn min(&self) -> [isize; 4] {
+        self.min
+    }
+
+    pub fn max(&self) -> [isize; 4] {
+        self.max
+    }
+
+    /// Panics if `coords` is outside the box.
+    pub fn get(&self, coords: [isize; 4]) -> BlockId {
+        self.blocks[self.index(coords)]
+    }
+
+    pub(super) fn set(&mut self, coords: [isize; 4], block: BlockId) {
+        let index = self.index(coords);
+        self.blocks[index] = block;
+    }
+
+    fn index(&self, coords: [isize; 4]) -> usize {
+        let mut index = 0;
+        for i in (0..4).rev() {
+            assert!(
+                (self.min[i]..self.max[i]).contains(&coords[i]),
+                "{:?} is outside the box",
+                coords
+            );
+            index =
+                index * (self.max[i] - self.min[i]) as usize + (coords[i] - self.min[i]) as usize;
+        }
+        index
+    }
+}
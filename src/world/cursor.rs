@@ -0,0 +1,118 @@
+use super::World;
+use crate::block::{BlockId, BlockState};
+use crate::chunk::Chunk;
+
+/// A position in the world that remembers the chunks around it.
+/// Stepping around and reading nearby blocks only looks up a chunk the first time it is needed.
+/// Chunks that are still nearby after the cursor crosses into another chunk are kept.
+///
+/// A cursor borrows the world, so code that edits the world as it walks, such as lighting, looks up each chunk itself.
+pub struct Cursor<'a> {
+    world: &'a World,
+    chunk_size: isize,
+
+    // The chunk containing the cursor, and the cursor's position within it.
+    chunk: [isize; 4],
+    rel_pos: [isize; 4],
+
+    // The chunks `chunk + offset`, for each `offset` in {-1, 0, 1}⁴, in base 3.
+    // `None` means the chunk has not been looked up yet.
+    neighbors: [Option<Option<&'a Chunk>>; 81],
+}
+
+impl<'a> Cursor<'a> {
+    pub(super) fn new(world: &'a World, coords: [isize; 4]) -> Self {
+        let (chunk, rel_pos) = world.config.chunk_position(coords);
+        Self {
+            world,
+            chunk_size: world.config.chunk_size as isize,
+
+            chunk,
+            rel_pos,
+
+            neighbors: [None; 81],
+        }
+    }
+
+    pub fn coords(&self) -> [isize; 4] {
+        let mut out = [0; 4];
+        for (i, coord) in out.iter_mut().enumerate() {
+            *coord = self.chunk[i] * self.chunk_size + self.rel_pos[i];
+        }
+        out
+    }
+
+    /// Move `distance` blocks along `axis`.
+    pub fn step(&mut self, axis: usize, distance: isize) {
+        self.rel_pos[axis] += distance;
+        let shift = self.rel_pos[axis].div_euclid(self.chunk_size);
+        if shift != 0 {
+            self.chunk[axis] += shift;
+            self.rel_pos[axis] = self.rel_pos[axis].rem_euclid(self.chunk_size);
+            self.shift_neighbors(axis, shift);
+        }
+    }
+
+    // Keep the chunks that are still neighbors after moving `shift` chunks along `axis`.
+    fn shift_neighbors(&mut self, axis: usize, shift: isize) {
+        let old = self.neighbors;
+        let place = 3_isize.pow(axis as u32);
+        for (i, neighbor) in self.neighbors.iter_mut().enumerate() {
+            let digit = i as isize / place % 3;
+            *neighbor = if (0..3).contains(&(digit + shift)) {
+                old[(i as isize + shift * place) as usize]
+            } else {
+                None
+            };
+        }
+    }
+
+    /// The block at `coords() + offset`, or `None` if its chunk has not been generated.
+    /// Each component of `offset` must be at most the chunk size in absolute value.
+    pub fn get(&mut self, offset: [isize; 4]) -> Option<BlockId> {
+        let (chunk, rel_pos) = self.chunk_at(offset);
+        chunk.map(|chunk| chunk[rel_pos])
+    }
+
+    /// The state of the block at `coords() + offset`, with the same limits as `get`.
+    pub fn get_state(&mut self, offset: [isize; 4]) -> Option<BlockState> {
+        let (chunk, rel_pos) = self.chunk_at(offset);
+        chunk.map(|chunk| chunk.state(rel_pos))
+    }
+
+    // The chunk containing `coords() + offset`, and the position within it.
+    fn chunk_at(&mut self, offset: [isize; 4]) -> (Option<&'a Chunk>, [isize; 4]) {
+        let mut which_neighbor = 0;
+        let mut rel_pos = [0; 4];
+        for i in (0..4).rev() {
+            debug_assert!(offset[i].abs() <= self.chunk_size);
+
+            let mut chunk_offset = 0;
+            rel_pos[i] = self.rel_pos[i] + offset[i];
+            if rel_pos[i] < 0 {
+                chunk_offset = -1;
+            } else if rel_pos[i] >= self.chunk_size {
+                chunk_offset = 1;
+            }
+            rel_pos[i] -= chunk_offset * self.chunk_size;
+            which_neighbor = 3 * which_neighbor + (chunk_offset + 1) as usize;
+        }
+
+        let chunk = match self.neighbors[which_neighbor] {
+            Some(chunk) => chunk,
+            None => {
+                let mut which_chunk = self.chunk;
+                let mut n = which_neighbor;
+                for c in which_chunk.iter_mut() {
+                    *c += (n % 3) as isize - 1;
+                    n /= 3;
+                }
+                let chunk = self.world.chunk(which_chunk);
+                self.neighbors[which_neighbor] = Some(chunk);
+                chunk
+            }
+        };
+
+        (chunk, rel_pos)
+    }
+}
@@ -0,0 +1,275 @@
+//! Water, lava and other fluids, which flow by cellular-automaton rules.
+//!
+//! Fluid falls straight down `w`, and otherwise spreads through the six neighbors in `x`, `y` and `z`,
+//! one block further from its source each time, until it is `reach` blocks away.
+//! Fluid that is not a source only stays while something still feeds it, so removing a source drains its flow.
+//!
+//! Whenever a block changes near fluid, every cell whose fluid depends on it is scheduled to be updated after the fluid's delay.
+//! `World::tick` runs the updates that are due, a limited number at a time.
+//! Updates to cells in chunks that are not loaded are dropped, so when a chunk loads, the cells along its faces are scheduled again.
+
+#[cfg(test)]
+mod tests;
+
+use super::{Cursor, World};
+use crate::block::{Block, BlockId, BlockState, Fluid};
+
+/// How far a fluid cell is from its source, stored in its block state.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Level {
+    /// Stays put, and feeds the cells around it. Placed and generated fluid is a source.
+    Source,
+    /// This many blocks sideways from the nearest source or falling fluid.
+    Flowing(u8),
+    /// Fed from directly above.
+    Falling,
+}
+
+impl Level {
+    const FALLING: u8 = 0x80;
+
+    fn from_state(state: BlockState) -> Self {
+        match state.0 {
+            0 => Level::Source,
+            Self::FALLING => Level::Falling,
+            n => Level::Flowing(n),
+        }
+    }
+
+    fn to_state(self) -> BlockState {
+        BlockState(match self {
+            Level::Source => 0,
+            Level::Flowing(n) => n,
+            Level::Falling => Self::FALLING,
+        })
+    }
+
+    // How many blocks away from the cells it feeds sideways.
+    fn distance(self) -> u8 {
+        match self {
+            Level::Source | Level::Falling => 0,
+            Level::Flowing(n) => n,
+        }
+    }
+}
+
+fn fluid(block: BlockId) -> Option<Fluid> {
+    block.info().fluid
+}
+
+fn offset(mut coords: [isize; 4], axis: usize, by: isize) -> [isize; 4] {
+    coords[axis] += by;
+    coords
+}
+
+// The eight face neighbors.
+fn neighbors(coords: [isize; 4]) -> impl Iterator<Item = [isize; 4]> {
+    (0..8).map(move |i| offset(coords, i / 2, if i % 2 == 0 { -1 } else { 1 }))
+}
+
+// The six neighbors in `x`, `y` and `z`.
+fn sideways(coords: [isize; 4]) -> impl Iterator<Item = [isize; 4]> {
+    (0..6).map(move |i| offset(coords, i / 2, if i % 2 == 0 { -1 } else { 1 }))
+}
+
+// The cells whose fluid depends on the block at `coords`, as read by `fluid_should_be`:
+// the one below, the sideways neighbors, and the cells above those, which may be fed by fluid that no longer pours down.
+fn dependents(coords: [isize; 4]) -> impl Iterator<Item = [isize; 4]> {
+    neighbors(coords).chain(sideways(offset(coords, 3, 1)))
+}
+
+// Whether flowing fluid at `cursor.coords() + at` pours into the cell below it, rather than spreading sideways.
+fn pours_down(cursor: &mut Cursor, at: [isize; 4]) -> bool {
+    let below = offset(at, 3, -1);
+    match cursor.get(below) {
+        Some(BlockId::AIR) => true,
+        Some(block) if fluid(block).is_some() => {
+            Level::from_state(cursor.get_state(below).unwrap()) == Level::Falling
+        }
+        _ => false,
+    }
+}
+
+impl World {
+    /// Advance the world by one tick, and run the fluid updates that are due, at most `budget` of them.
+    /// Updates left over stay due, and are run first on the next tick.
+    /// Fluid delays are counted in ticks, so the world should be ticked at a steady rate.
+    pub fn tick(&mut self, budget: usize) {
+        self.tick += 1;
+        for _ in 0..budget {
+            let (&due, cells) = match self.fluid_updates.iter_mut().next() {
+                Some(entry) => entry,
+                None => break,
+            };
+            if due > self.tick {
+                break;
+            }
+            let coords = cells.pop().unwrap();
+            if cells.is_empty() {
+                self.fluid_updates.remove(&due);
+            }
+            self.scheduled.remove(&coords);
+            self.update_fluid(coords);
+        }
+    }
+
+    // Update the cell at `coords` after `delay` ticks, unless it is already waiting for an update.
+    fn schedule(&mut self, coords: [isize; 4], delay: u64) {
+        if self.scheduled.insert(coords) {
+            self.fluid_updates
+                .entry(self.tick + delay)
+                .or_default()
+                .push(coords);
+        }
+    }
+
+    /// Schedule the updates needed now that the block at canonical `coords` changed from `old` to `new`.
+    pub(super) fn schedule_fluid(&mut self, coords: [isize; 4], old: BlockId, new: BlockId) {
+        // Far from any fluid, nothing can flow differently.
+        let nearby = match fluid(new)
+            .or_else(|| fluid(old))
+            .or_else(|| neighbors(coords).find_map(|neighbor| self.get(neighbor).and_then(fluid)))
+        {
+            Some(nearby) => nearby,
+            None => return,
+        };
+
+        self.schedule_around(coords, nearby);
+    }
+
+    // The cell at canonical `coords` and its dependents may now fill, drain, or flow somewhere else.
+    // Cells without fluid of their own are updated after the delay of `nearby`.
+    fn schedule_around(&mut self, coords: [isize; 4], nearby: Fluid) {
+        self.schedule(coords, nearby.delay);
+        for cell in dependents(coords) {
+            let flow = self.get(cell).and_then(fluid).unwrap_or(nearby);
+            if let Some(cell) = self.canonical_coords(cell) {
+                self.schedule(cell, flow.delay);
+            }
+        }
+    }
+
+    /// Schedule the updates that were dropped while the canonical chunk `which_chunk` was not loaded:
+    /// wherever fluid meets its faces, unless it is a source with only more fluid across the face.
+    pub(super) fn schedule_chunk_faces(&mut self, which_chunk: [isize; 4]) {
+        let size = self.config.chunk_size as isize;
+        let mut corner = which_chunk;
+        for c in corner.iter_mut() {
+            *c *= size;
+        }
+
+        let mut around = Vec::new();
+        for axis in 0..4 {
+            for &(inside, outside) in &[(0, -1), (size - 1, size)] {
+                let mut face_max = [size; 4];
+                face_max[axis] = 1;
+                for x in 0..face_max[0] {
+                    for y in 0..face_max[1] {
+                        for z in 0..face_max[2] {
+                            for w in 0..face_max[3] {
+                                let mut a =
+                                    [corner[0] + x, corner[1] + y, corner[2] + z, corner[3] + w];
+                                a[axis] = corner[axis] + inside;
+                                let mut b = a;
+                                b[axis] = corner[axis] + outside;
+                                let (a_block, b_block) = match (self.get(a), self.get(b)) {
+                                    (Some(a_block), Some(b_block)) => (a_block, b_block),
+                                    _ => continue,
+                                };
+                                let flowing = |coords, block| {
+                                    fluid(block).filter(|_| {
+                                        Level::from_state(self.get_state(coords).unwrap())
+                                            != Level::Source
+                                    })
+                                };
+                                let flow = flowing(a, a_block)
+                                    .or_else(|| flowing(b, b_block))
+                                    .or_else(|| match (a_block, b_block) {
+                                        (BlockId::AIR, other) | (other, BlockId::AIR) => {
+                                            fluid(other)
+                                        }
+                                        _ => None,
+                                    });
+                                let flow = match flow {
+                                    Some(flow) => flow,
+                                    None => continue,
+                                };
+                                around.push((a, flow));
+                                if let Some(b) = self.canonical_coords(b) {
+                                    around.push((b, flow));
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        for (coords, flow) in around {
+            self.schedule_around(coords, flow);
+        }
+    }
+
+    // What should be at `coords`, if it can hold fluid at all: a fluid and its level, or nothing.
+    fn fluid_should_be(&self, coords: [isize; 4]) -> Option<(BlockId, Level)> {
+        let mut cursor = self.cursor(coords);
+        if let Some(above) = cursor.get(offset([0; 4], 3, 1)) {
+            if fluid(above).is_some() {
+                return Some((above, Level::Falling));
+            }
+        }
+
+        let mut best: Option<(BlockId, u8)> = None;
+        for neighbor in sideways([0; 4]) {
+            let block = match cursor.get(neighbor) {
+                Some(block) => block,
+                None => continue,
+            };
+            let flow = match fluid(block) {
+                Some(flow) => flow,
+                None => continue,
+            };
+            let level = Level::from_state(cursor.get_state(neighbor).unwrap());
+            if level != Level::Source && pours_down(&mut cursor, neighbor) {
+                continue;
+            }
+            let distance = level.distance() + 1;
+            if distance <= flow.reach && best.is_none_or(|(_, best)| distance < best) {
+                best = Some((block, distance));
+            }
+        }
+        best.map(|(block, distance)| (block, Level::Flowing(distance)))
+    }
+
+    fn update_fluid(&mut self, coords: [isize; 4]) {
+        let (block, state) = match (self.get(coords), self.get_state(coords)) {
+            (Some(block), Some(state)) => (block, state),
+            // The chunk has been evicted since the update was scheduled.
+            _ => return,
+        };
+
+        // Only empty cells and flowing fluid change.
+        let current = match fluid(block) {
+            Some(_) => match Level::from_state(state) {
+                Level::Source => return,
+                level => Some((block, level)),
+            },
+            None if block == BlockId::AIR => None,
+            None => return,
+        };
+
+        let target = self.fluid_should_be(coords);
+        if target == current {
+            return;
+        }
+        match target {
+            Some((fluid, level)) if Some(fluid) == current.map(|(block, _)| block) => {
+                self.set_state(coords, level.to_state())
+            }
+            // Flowing fluid is not worth keeping, so it is destroyed when it drains or is displaced.
+            Some((fluid, level)) => {
+                drop(self.replace(coords, Block::with_state(fluid, level.to_state())))
+            }
+            None => drop(self.replace(coords, Block::create(BlockId::AIR))),
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use super::Level;
+use crate::block::{registry, Block, BlockId};
+use crate::config::Config;
+use crate::world::{Budget, Topology, World};
+
+use std::collections::HashMap;
+
+// Flat stone up to `w = 0`, and air above.
+fn world() -> World {
+    let generator = crate::terrain::create("flat", &|key| match key {
+        "layers" => Some("stone".to_owned()),
+        _ => None,
+    })
+    .unwrap();
+    let mut world = World::new(
+        Config::new(8, 4),
+        Topology::UNBOUNDED,
+        generator,
+        Budget::Unlimited,
+        Box::new(HashMap::new()),
+    );
+    world.load_chunks([-2; 4], [2; 4]);
+    world
+}
+
+fn lava() -> BlockId {
+    registry().id("lava").unwrap()
+}
+
+fn place(world: &mut World, coords: [isize; 4], block: BlockId) {
+    drop(world.replace(coords, Block::create(block)));
+}
+
+// Tick until no updates are left.
+fn settle(world: &mut World) {
+    for _ in 0..10_000 {
+        if world.scheduled.is_empty() {
+            return;
+        }
+        world.tick(usize::MAX);
+    }
+    panic!("the fluid never settled");
+}
+
+// How many updates are due now.
+fn due(world: &World) -> usize {
+    world
+        .fluid_updates
+        .range(..=world.tick)
+        .map(|(_, cells)| cells.len())
+        .sum()
+}
+
+fn fluid_at(world: &World, coords: [isize; 4]) -> Option<(BlockId, Level)> {
+    let block = world.get(coords).unwrap();
+    block
+        .info()
+        .fluid
+        .map(|_| (block, Level::from_state(world.get_state(coords).unwrap())))
+}
+
+// Every cell with `min[i] <= coords[i] < max[i]` that fluid can flow into holds what the rules say it should.
+fn assert_settled(world: &World, min: [isize; 4], max: [isize; 4]) {
+    for x in min[0]..max[0] {
+        for y in min[1]..max[1] {
+            for z in min[2]..max[2] {
+                for w in min[3]..max[3] {
+                    let coords = [x, y, z, w];
+                    let current = fluid_at(world, coords);
+                    let open = match current {
+                        Some((_, level)) => level != Level::Source,
+                        None => world.get(coords) == Some(BlockId::AIR),
+                    };
+                    if open {
+                        assert_eq!(current, world.fluid_should_be(coords), "at {:?}", coords);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn water_spreads_as_far_as_its_reach() {
+    let mut world = world();
+    place(&mut world, [0, 0, 0, 1], BlockId::WATER);
+    settle(&mut world);
+
+    assert_eq!(
+        fluid_at(&world, [0, 0, 0, 1]),
+        Some((BlockId::WATER, Level::Source))
+    );
+    for distance in 1..=7 {
+        assert_eq!(
+            fluid_at(&world, [distance, 0, 0, 1]),
+            Some((BlockId::WATER, Level::Flowing(distance as u8)))
+        );
+    }
+    assert_eq!(fluid_at(&world, [8, 0, 0, 1]), None);
+    assert_eq!(
+        fluid_at(&world, [-3, 2, -1, 1]),
+        Some((BlockId::WATER, Level::Flowing(6)))
+    );
+    assert_eq!(fluid_at(&world, [-3, 2, -3, 1]), None);
+    // Fluid does not flow up `w`.
+    assert_eq!(fluid_at(&world, [0, 0, 0, 2]), None);
+}
+
+#[test]
+fn removing_the_source_drains_the_flow() {
+    let mut world = world();
+    place(&mut world, [0, 0, 0, 1], BlockId::WATER);
+    settle(&mut world);
+    place(&mut world, [0, 0, 0, 1], BlockId::AIR);
+    settle(&mut world);
+
+    for x in -8..=8 {
+        for y in -8..=8 {
+            assert_eq!(fluid_at(&world, [x, y, 0, 1]), None);
+        }
+    }
+}
+
+#[test]
+fn fluid_falls_down_w_and_spreads_where_it_lands() {
+    let mut world = world();
+    place(&mut world, [0, 0, 0, 6], BlockId::WATER);
+    settle(&mut world);
+
+    for w in 1..6 {
+        assert_eq!(
+            fluid_at(&world, [0, 0, 0, w]),
+            Some((BlockId::WATER, Level::Falling))
+        );
+    }
+    // The source also spreads a block sideways, and that falls too.
+    assert_eq!(
+        fluid_at(&world, [0, 0, 1, 3]),
+        Some((BlockId::WATER, Level::Falling))
+    );
+    assert_eq!(fluid_at(&world, [0, 0, 2, 3]), None);
+    assert_eq!(
+        fluid_at(&world, [0, 0, 4, 1]),
+        Some((BlockId::WATER, Level::Flowing(3)))
+    );
+}
+
+#[test]
+fn fluid_flows_into_an_opened_gap() {
+    let mut world = world();
+    place(&mut world, [1, 0, 0, 1], BlockId::STONE);
+    place(&mut world, [0, 0, 0, 1], BlockId::WATER);
+    settle(&mut world);
+    // The long way round.
+    assert_eq!(
+        fluid_at(&world, [2, 0, 0, 1]),
+        Some((BlockId::WATER, Level::Flowing(4)))
+    );
+
+    place(&mut world, [1, 0, 0, 1], BlockId::AIR);
+    settle(&mut world);
+    assert_eq!(
+        fluid_at(&world, [1, 0, 0, 1]),
+        Some((BlockId::WATER, Level::Flowing(1)))
+    );
+    assert_eq!(
+        fluid_at(&world, [2, 0, 0, 1]),
+        Some((BlockId::WATER, Level::Flowing(2)))
+    );
+}
+
+#[test]
+fn fluid_flows_around_a_hole_it_pours_into() {
+    let mut world = world();
+    place(&mut world, [0, 0, 0, 1], BlockId::WATER);
+    settle(&mut world);
+    place(&mut world, [1, 0, 0, 0], BlockId::AIR);
+    settle(&mut world);
+
+    assert_eq!(
+        fluid_at(&world, [1, 0, 0, 0]),
+        Some((BlockId::WATER, Level::Falling))
+    );
+    // Nothing spreads past the hole, so the far side is fed the long way round.
+    assert_eq!(
+        fluid_at(&world, [2, 0, 0, 1]),
+        Some((BlockId::WATER, Level::Flowing(4)))
+    );
+    assert_settled(&world, [-8, -8, -8, 0], [9, 9, 9, 3]);
+}
+
+#[test]
+fn fluid_settles_as_the_rules_say_after_any_edits() {
+    let mut world = world();
+    // A linear congruential generator, so that the edits are the same every run.
+    let mut seed = 1_u64;
+    let mut random = |n: u64| {
+        seed = seed
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (seed >> 33) % n
+    };
+    let blocks = [BlockId::WATER, BlockId::STONE, BlockId::AIR];
+
+    for _ in 0..10 {
+        for _ in 0..10 {
+            let coords = [
+                random(9) as isize - 4,
+                random(9) as isize - 4,
+                random(9) as isize - 4,
+                random(4) as isize,
+            ];
+            place(&mut world, coords, blocks[random(3) as usize]);
+        }
+        settle(&mut world);
+        assert_settled(&world, [-12, -12, -12, -1], [13, 13, 13, 5]);
+    }
+
+    // Lava glows, so every change to it relights a large region. One source is enough to see how it meets the water.
+    place(&mut world, [4, 0, 0, 1], lava());
+    settle(&mut world);
+    assert_settled(&world, [-12, -12, -12, -1], [13, 13, 13, 5]);
+}
+
+#[test]
+fn fluid_flows_on_into_chunks_loaded_later() {
+    let mut world = world();
+    // The loaded chunks end at `x = 16`.
+    place(&mut world, [13, 0, 0, 1], BlockId::WATER);
+    settle(&mut world);
+    assert_eq!(world.get([16, 0, 0, 1]), None);
+
+    world.load_chunks([2, -2, -2, -2], [3, 2, 2, 2]);
+    settle(&mut world);
+    for distance in 1..=7 {
+        assert_eq!(
+            fluid_at(&world, [13 + distance, 0, 0, 1]),
+            Some((BlockId::WATER, Level::Flowing(distance as u8)))
+        );
+    }
+    assert_settled(&world, [6, -8, -8, 0], [24, 9, 9, 3]);
+}
+
+#[test]
+fn lava_flows_slower_and_not_as_far() {
+    let mut world = world();
+    place(&mut world, [0, 0, 0, 1], lava());
+    let delay = lava().info().fluid.unwrap().delay;
+
+    for _ in 1..delay {
+        world.tick(usize::MAX);
+    }
+    assert_eq!(fluid_at(&world, [1, 0, 0, 1]), None);
+    world.tick(usize::MAX);
+    assert_eq!(
+        fluid_at(&world, [1, 0, 0, 1]),
+        Some((lava(), Level::Flowing(1)))
+    );
+
+    settle(&mut world);
+    assert_eq!(
+        fluid_at(&world, [3, 0, 0, 1]),
+        Some((lava(), Level::Flowing(3)))
+    );
+    assert_eq!(fluid_at(&world, [4, 0, 0, 1]), None);
+}
+
+#[test]
+fn each_tick_runs_at_most_its_budget() {
+    let mut world = world();
+    place(&mut world, [0, 0, 0, 1], BlockId::WATER);
+    let delay = BlockId::WATER.info().fluid.unwrap().delay;
+
+    for _ in 1..delay {
+        world.tick(0);
+    }
+    // The source itself, its eight neighbors, and the six cells diagonally above it.
+    world.tick(2);
+    assert_eq!(due(&world), 13);
+
+    // The updates left over run on the next tick.
+    world.tick(usize::MAX);
+    assert_eq!(due(&world), 0);
+    for neighbor in super::sideways([0, 0, 0, 1]) {
+        assert_eq!(world.get(neighbor), Some(BlockId::WATER));
+    }
+}
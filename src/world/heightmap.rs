@@ -0,0 +1,143 @@
+use crate::chunk::Chunk;
+use crate::terrain::TerrainGenerator;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The highest opaque block in each `w` column of a chunk, so that sky light can find the top surface without searching.
+pub struct Heightmap {
+    size: usize,
+    // For each column, one more than the relative `w` of its highest opaque block, or zero if it has none.
+    tops: Vec<u8>,
+}
+
+impl Heightmap {
+    pub fn new(chunk: &Chunk, size: usize) -> Self {
+        let mut tops = vec![0; size.pow(3)];
+        chunk.for_each_in([0; 4], [size; 4], |[x, y, z, w], block| {
+            if block.info().opaque {
+                let top = &mut tops[x + size * (y + size * z)];
+                *top = (*top).max(w as u8 + 1);
+            }
+        });
+        Self { size, tops }
+    }
+
+    /// The relative `w` of the highest opaque block in the column, if there is one.
+    pub fn top(&self, [x, y, z]: [usize; 3]) -> Option<usize> {
+        match self.tops[x + self.size * (y + self.size * z)] {
+            0 => None,
+            top => Some(top as usize - 1),
+        }
+    }
+
+    /// Bring the column containing `rel_pos` up to date, after the block there changed.
+    pub fn update(&mut self, chunk: &Chunk, rel_pos: [isize; 4]) {
+        let [x, y, z, _] = rel_pos;
+        let mut top = 0;
+        for w in (0..self.size).rev() {
+            if chunk[[x, y, z, w as isize]].info().opaque {
+                top = w as u8 + 1;
+                break;
+            }
+        }
+        self.tops[x as usize + self.size * (y as usize + self.size * z as usize)] = top;
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        std::mem::size_of::<Self>() + self.tops.len()
+    }
+}
+
+/// Where the sky begins over each `w` column in a column of chunks, which sky light must know whether or not the chunks above are loaded.
+///
+/// While any chunk in the column is loaded, the heightmap of every chunk that has been loaded in it is kept,
+/// so that a cave stays dark after the chunks over it are dropped.
+/// Once none are, only the heightmaps of modified chunks are kept, since the generator's surface stands in for the rest.
+pub struct Column {
+    size: usize,
+    // Where the generator puts the ground in each `w` column.
+    surface: Vec<Option<isize>>,
+    // By the chunk's `w` coordinate.
+    heightmaps: BTreeMap<isize, Heightmap>,
+    // How many chunks in the column are loaded.
+    loaded: usize,
+    // The chunks in `heightmaps` that were evicted without being modified.
+    unmodified: BTreeSet<isize>,
+}
+
+impl Column {
+    pub fn new(generator: &dyn TerrainGenerator, size: usize, [x, y, z]: [isize; 3]) -> Self {
+        let corner = [x * size as isize, y * size as isize, z * size as isize];
+        let mut surface = Vec::with_capacity(size.pow(3));
+        for z in 0..size as isize {
+            for y in 0..size as isize {
+                for x in 0..size as isize {
+                    surface.push(generator.surface([corner[0] + x, corner[1] + y, corner[2] + z]));
+                }
+            }
+        }
+        Self {
+            size,
+            surface,
+            heightmaps: BTreeMap::new(),
+            loaded: 0,
+            unmodified: BTreeSet::new(),
+        }
+    }
+
+    /// Record the heightmap of the chunk at `w` in the column, which has just been loaded.
+    pub fn insert(&mut self, w: isize, heightmap: Heightmap) {
+        self.heightmaps.insert(w, heightmap);
+        self.unmodified.remove(&w);
+        self.loaded += 1;
+    }
+
+    /// Note that the chunk at `w` in the column has been evicted.
+    /// Returns whether nothing is left worth keeping, so that the column can be dropped.
+    pub fn evict(&mut self, w: isize, modified: bool) -> bool {
+        self.loaded -= 1;
+        if !modified {
+            self.unmodified.insert(w);
+        }
+        if self.loaded == 0 {
+            for w in std::mem::take(&mut self.unmodified) {
+                self.heightmaps.remove(&w);
+            }
+        }
+        self.heightmaps.is_empty()
+    }
+
+    pub fn heightmap_mut(&mut self, w: isize) -> Option<&mut Heightmap> {
+        self.heightmaps.get_mut(&w)
+    }
+
+    /// The `w` coordinate of the lowest block in any chunk of the column that has been loaded.
+    pub fn bottom(&self) -> Option<isize> {
+        self.heightmaps
+            .keys()
+            .next()
+            .map(|&w| w * self.size as isize)
+    }
+
+    /// The `w` coordinate of the highest opaque block in the `w` column, as far as is known, or `None` if there is none.
+    /// The generator's surface counts until the chunk it is in has been loaded, and the heightmaps say what is really there.
+    pub fn top(&self, [x, y, z]: [usize; 3]) -> Option<isize> {
+        let size = self.size as isize;
+        let known = self.heightmaps.iter().rev().find_map(|(&w, heightmap)| {
+            heightmap.top([x, y, z]).map(|top| w * size + top as isize)
+        });
+        let surface = self.surface[x + self.size * (y + self.size * z)]
+            .filter(|surface| !self.heightmaps.contains_key(&surface.div_euclid(size)));
+        known.max(surface)
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.surface.len() * std::mem::size_of::<Option<isize>>()
+            + self
+                .heightmaps
+                .values()
+                .map(Heightmap::memory_usage)
+                .sum::<usize>()
+    }
+}
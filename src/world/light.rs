@@ -0,0 +1,509 @@
+//! Block light and sky light, spread by flood fill.
+//!
+//! Each cell has a level of each kind of light, from 0 to 15.
+//! A cell's block light is the brightest of its own block's glow,
+//! and one less than the brightest of its eight face neighbors, unless its block is opaque.
+//! Sky light is the same, except that nothing glows, and every cell above the highest opaque block in its `w` column is fully lit.
+//! The highest block is taken from the heightmaps of the chunks that have been loaded there,
+//! and from the generator's surface until the chunk it is in has been loaded,
+//! so a cave stays dark whether or not the chunks above it are loaded.
+//!
+//! Light only spreads through loaded chunks. When a chunk loads, light flows into it from its neighbors and out of it again.
+
+use super::heightmap::{Column, Heightmap};
+use super::World;
+use crate::block::BlockId;
+
+use std::collections::VecDeque;
+
+pub const MAX_LIGHT: u8 = 15;
+
+/// The light of every cell in a chunk, in the same order as the chunk's blocks in a texture.
+/// Each byte holds the block light in its low four bits, and the sky light in its high four bits.
+pub enum Light {
+    Uniform(u8),
+    Dense(Vec<u8>),
+}
+
+impl Light {
+    fn get(&self, index: usize) -> u8 {
+        match self {
+            Light::Uniform(light) => *light,
+            Light::Dense(light) => light[index],
+        }
+    }
+
+    fn set(&mut self, index: usize, light: u8, volume: usize) {
+        if let Light::Uniform(uniform) = *self {
+            if uniform == light {
+                return;
+            }
+            *self = Light::Dense(vec![uniform; volume]);
+        }
+        if let Light::Dense(lights) = self {
+            lights[index] = light;
+        }
+    }
+
+    pub fn pass_as_slice(&self, volume: usize, f: impl FnOnce(&[u8])) {
+        match self {
+            Light::Uniform(light) => f(&vec![*light; volume]),
+            Light::Dense(light) => f(light),
+        }
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + match self {
+                Light::Uniform(_) => 0,
+                Light::Dense(light) => light.len(),
+            }
+    }
+}
+
+// Lights up the chunks beyond a wall, which nothing can reach.
+static DARK: Light = Light::Uniform(0);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Channel {
+    Block,
+    Sky,
+}
+
+impl Channel {
+    const ALL: [Self; 2] = [Channel::Block, Channel::Sky];
+
+    fn shift(self) -> u8 {
+        match self {
+            Channel::Block => 0,
+            Channel::Sky => 4,
+        }
+    }
+
+    fn get(self, light: u8) -> u8 {
+        (light >> self.shift()) & MAX_LIGHT
+    }
+
+    fn with(self, light: u8, level: u8) -> u8 {
+        light & !(MAX_LIGHT << self.shift()) | level << self.shift()
+    }
+}
+
+fn emission(block: BlockId) -> u8 {
+    block.info().light.min(MAX_LIGHT)
+}
+
+// The eight face neighbors.
+fn neighbors(coords: [isize; 4]) -> impl Iterator<Item = [isize; 4]> {
+    (0..8).map(move |i| {
+        let mut out = coords;
+        out[i / 2] += if i % 2 == 0 { -1 } else { 1 };
+        out
+    })
+}
+
+// The neighbors of the cell at `index` in a chunk of width `n`, that are in the chunk too, by their indices.
+fn within(n: usize, index: usize) -> impl Iterator<Item = usize> {
+    (0..8).filter_map(move |i| {
+        let stride = n.pow(i as u32 / 2);
+        let along = index / stride % n;
+        if i % 2 == 0 && along > 0 {
+            Some(index - stride)
+        } else if i % 2 == 1 && along < n - 1 {
+            Some(index + stride)
+        } else {
+            None
+        }
+    })
+}
+
+// Spread light outward from each cell in the queue, by index, without leaving the chunk.
+fn spread_within(
+    light: &mut [u8],
+    opaque: &[bool],
+    n: usize,
+    mut queue: VecDeque<usize>,
+    channel: Channel,
+) {
+    while let Some(i) = queue.pop_front() {
+        let level = channel.get(light[i]);
+        if level <= 1 {
+            continue;
+        }
+        for j in within(n, i) {
+            if !opaque[j] && channel.get(light[j]) < level - 1 {
+                light[j] = channel.with(light[j], level - 1);
+                queue.push_back(j);
+            }
+        }
+    }
+}
+
+impl World {
+    /// The block light in the low four bits, and the sky light in the high four bits.
+    /// Returns `None` if the cell's chunk is not loaded.
+    pub fn light(&self, coords: [isize; 4]) -> Option<u8> {
+        let (which_chunk, rel_pos) = self.config.chunk_position(coords);
+        self.chunk_light(which_chunk)
+            .map(|light| light.get(self.light_index(rel_pos)))
+    }
+
+    /// Chunks beyond a wall are dark.
+    pub fn chunk_light(&self, which_chunk: [isize; 4]) -> Option<&Light> {
+        match self.topology.canonical_chunk(which_chunk) {
+            Some(which_chunk) => self.chunks.get(&which_chunk).map(|loaded| &loaded.light),
+            None => Some(&DARK),
+        }
+    }
+
+    fn light_index(&self, [x, y, z, w]: [isize; 4]) -> usize {
+        let size = self.config.chunk_size;
+        x as usize + size * (y as usize + size * (z as usize + size * w as usize))
+    }
+
+    // The block and light level at `coords`, or `None` if they are beyond a wall or not loaded.
+    fn light_cell(&self, coords: [isize; 4], channel: Channel) -> Option<(BlockId, u8)> {
+        let coords = self.canonical_coords(coords)?;
+        let (which_chunk, rel_pos) = self.config.chunk_position(coords);
+        let loaded = self.chunks.get(&which_chunk)?;
+        Some((
+            loaded.chunk[rel_pos],
+            channel.get(loaded.light.get(self.light_index(rel_pos))),
+        ))
+    }
+
+    // Does nothing if `coords` is not loaded.
+    fn set_light(&mut self, coords: [isize; 4], channel: Channel, level: u8) {
+        let coords = match self.canonical_coords(coords) {
+            Some(coords) => coords,
+            None => return,
+        };
+        let (which_chunk, rel_pos) = self.config.chunk_position(coords);
+        let index = self.light_index(rel_pos);
+        let volume = self.config.chunk_volume();
+        if let Some(loaded) = self.chunks.get_mut(&which_chunk) {
+            let old = loaded.light.get(index);
+            let new = channel.with(old, level);
+            if old != new {
+                loaded.light.set(index, new, volume);
+                self.changes
+                    .light
+                    .entry(which_chunk)
+                    .or_default()
+                    .insert(coords);
+            }
+        }
+    }
+
+    // The `w` coordinate of the highest opaque block in the `w` column through canonical `coords`, as far as is known.
+    pub(super) fn sky_top(&self, coords: [isize; 4]) -> Option<isize> {
+        let (which_chunk, [x, y, z, _]) = self.config.chunk_position(coords);
+        self.columns
+            .get(&[which_chunk[0], which_chunk[1], which_chunk[2]])
+            .and_then(|column| column.top([x as usize, y as usize, z as usize]))
+    }
+
+    // The light that a cell gets regardless of its neighbors.
+    fn light_source(&self, coords: [isize; 4], block: BlockId, channel: Channel) -> u8 {
+        match channel {
+            Channel::Block => emission(block),
+            Channel::Sky => match self.canonical_coords(coords) {
+                Some(coords) if !block.info().opaque && Some(coords[3]) > self.sky_top(coords) => {
+                    MAX_LIGHT
+                }
+                _ => 0,
+            },
+        }
+    }
+
+    /// Spread light outward from each cell in the queue.
+    fn spread_light(&mut self, mut queue: VecDeque<[isize; 4]>, channel: Channel) {
+        while let Some(coords) = queue.pop_front() {
+            let level = match self.light_cell(coords, channel) {
+                Some((_, level)) if level > 0 => level,
+                _ => continue,
+            };
+            for neighbor in neighbors(coords) {
+                if let Some((block, neighbor_level)) = self.light_cell(neighbor, channel) {
+                    if !block.info().opaque && neighbor_level < level - 1 {
+                        self.set_light(neighbor, channel, level - 1);
+                        queue.push_back(neighbor);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Darken everything that may have been lit from the given cells, each with the light level it had.
+    /// Returns the cells that still have light, from which to spread light back into the darkened region.
+    fn remove_light(
+        &mut self,
+        cells: Vec<([isize; 4], u8)>,
+        channel: Channel,
+    ) -> VecDeque<[isize; 4]> {
+        let mut remove = VecDeque::new();
+        let mut refill = VecDeque::new();
+        for (coords, level) in cells {
+            self.set_light(coords, channel, 0);
+            remove.push_back((coords, level));
+        }
+
+        while let Some((coords, level)) = remove.pop_front() {
+            for neighbor in neighbors(coords) {
+                let (block, neighbor_level) = match self.light_cell(neighbor, channel) {
+                    Some(cell) => cell,
+                    None => continue,
+                };
+                if neighbor_level == 0 {
+                    continue;
+                }
+                if neighbor_level < level {
+                    // The neighbor's light may have come from here. Keep only what it gets regardless.
+                    let source = self.light_source(neighbor, block, channel);
+                    self.set_light(neighbor, channel, source);
+                    if source > 0 {
+                        refill.push_back(neighbor);
+                    }
+                    remove.push_back((neighbor, neighbor_level));
+                } else {
+                    refill.push_back(neighbor);
+                }
+            }
+        }
+
+        refill
+    }
+
+    // The highest opaque block in the `w` column through canonical `coords` moved from `old` to `new`,
+    // so the loaded cells in between have come out from under the sky, or gone under it.
+    // Lights the cells now under open sky, adding them to `lit`, and returns the cells that lost it, with their levels.
+    fn move_sky_top(
+        &mut self,
+        coords: [isize; 4],
+        old: Option<isize>,
+        new: Option<isize>,
+        lit: &mut VecDeque<[isize; 4]>,
+    ) -> Vec<([isize; 4], u8)> {
+        let mut darkened = Vec::new();
+        let high = match old.max(new) {
+            Some(high) if old != new => high,
+            _ => return darkened,
+        };
+        let (which_chunk, _) = self.config.chunk_position(coords);
+        let low = match old.min(new) {
+            Some(low) => low + 1,
+            None => {
+                match self.columns[&[which_chunk[0], which_chunk[1], which_chunk[2]]].bottom() {
+                    Some(bottom) => bottom,
+                    None => return darkened,
+                }
+            }
+        };
+        for w in low..=high {
+            let cell = [coords[0], coords[1], coords[2], w];
+            let (block, level) = match self.light_cell(cell, Channel::Sky) {
+                Some(cell) => cell,
+                None => continue,
+            };
+            if self.light_source(cell, block, Channel::Sky) == MAX_LIGHT {
+                self.set_light(cell, Channel::Sky, MAX_LIGHT);
+                lit.push_back(cell);
+            } else if level > 0 {
+                darkened.push((cell, level));
+            }
+        }
+        darkened
+    }
+
+    /// Bring the light up to date after the block at `coords` changed.
+    /// `old_sky` is what `sky_top` said about its column before the change.
+    pub(super) fn update_light(&mut self, coords: [isize; 4], old_sky: Option<isize>) {
+        for &channel in &Channel::ALL {
+            let (block, level) = match self.light_cell(coords, channel) {
+                Some(cell) => cell,
+                None => return,
+            };
+
+            let mut lit = VecDeque::new();
+            let mut darkened = Vec::new();
+            if level > 0 {
+                darkened.push((coords, level));
+            }
+            if channel == Channel::Sky {
+                let new_sky = self.sky_top(coords);
+                darkened.extend(self.move_sky_top(coords, old_sky, new_sky, &mut lit));
+            }
+            let mut refill = self.remove_light(darkened, channel);
+            refill.extend(lit);
+
+            let source = self.light_source(coords, block, channel);
+            if source > 0 {
+                self.set_light(coords, channel, source);
+                refill.push_back(coords);
+            }
+            if !block.info().opaque {
+                refill.extend(neighbors(coords));
+            }
+
+            self.spread_light(refill, channel);
+        }
+    }
+
+    /// Light a freshly loaded chunk, by its own glowing blocks, the sky, and its neighbors' light, and let its light out.
+    /// The chunk itself is sent to the renderer whole, so only changes to other chunks are recorded.
+    pub(super) fn light_new_chunk(&mut self, which_chunk: [isize; 4]) {
+        let n = self.config.chunk_size;
+        let size = n as isize;
+        let mut corner = which_chunk;
+        for c in corner.iter_mut() {
+            *c *= size;
+        }
+        let world_coords = |[x, y, z, w]: [usize; 4]| {
+            [
+                corner[0] + x as isize,
+                corner[1] + y as isize,
+                corner[2] + z as isize,
+                corner[3] + w as isize,
+            ]
+        };
+        // Indices into the chunk's light.
+        let index = |[x, y, z, w]: [usize; 4]| x + n * (y + n * (z + n * w));
+
+        // Record the chunk's heightmap, and see where that moves the sky.
+        let column = [which_chunk[0], which_chunk[1], which_chunk[2]];
+        if !self.columns.contains_key(&column) {
+            let new_column = Column::new(&*self.generator, n, column);
+            self.columns.insert(column, new_column);
+        }
+        let column_tops = |world: &Self| {
+            let mut tops = Vec::with_capacity(n.pow(3));
+            for z in 0..n {
+                for y in 0..n {
+                    for x in 0..n {
+                        tops.push(world.sky_top(world_coords([x, y, z, 0])));
+                    }
+                }
+            }
+            tops
+        };
+        let old_tops = column_tops(self);
+        let heightmap = Heightmap::new(&self.chunks[&which_chunk].chunk, n);
+        self.columns
+            .get_mut(&column)
+            .unwrap()
+            .insert(which_chunk[3], heightmap);
+        let tops = column_tops(self);
+
+        // The chunk's own light: its glowing blocks, and full sky light above the highest opaque block in each column.
+        // This is worked out and spread on the chunk itself, and only crosses into the world at its faces.
+        let all_open = tops.iter().all(|&top| top < Some(corner[3]));
+        let mut light = vec![0; n.pow(4)];
+        let mut opaque = vec![false; n.pow(4)];
+        let mut glowing = VecDeque::new();
+        self.chunks[&which_chunk]
+            .chunk
+            .for_each_in([0; 4], [n; 4], |rel_pos, block| {
+                let i = index(rel_pos);
+                opaque[i] = block.info().opaque;
+                let glow = emission(block);
+                if glow > 0 {
+                    light[i] = glow;
+                    glowing.push_back(i);
+                }
+                let [x, y, z, w] = rel_pos;
+                if all_open || Some(corner[3] + w as isize) > tops[x + n * (y + n * z)] {
+                    light[i] |= MAX_LIGHT << Channel::Sky.shift();
+                }
+            });
+
+        // Sky light only needs to spread from cells next to darker ones.
+        let mut open = VecDeque::new();
+        if !all_open {
+            let sky = |light: u8| Channel::Sky.get(light) == MAX_LIGHT;
+            for i in 0..n.pow(4) {
+                if sky(light[i]) && within(n, i).any(|j| !sky(light[j]) && !opaque[j]) {
+                    open.push_back(i);
+                }
+            }
+        }
+        spread_within(&mut light, &opaque, n, glowing, Channel::Block);
+        spread_within(&mut light, &opaque, n, open, Channel::Sky);
+
+        let light = if light.iter().all(|&l| l == light[0]) {
+            Light::Uniform(light[0])
+        } else {
+            Light::Dense(light)
+        };
+        self.chunks.get_mut(&which_chunk).unwrap().light = light;
+
+        // If the chunk moved the highest block in a column, the cells it passed in other chunks must be brightened or darkened.
+        let mut block_queue = VecDeque::new();
+        let mut sky_queue = VecDeque::new();
+        let mut darkened = Vec::new();
+        for z in 0..n {
+            for y in 0..n {
+                for x in 0..n {
+                    let i = x + n * (y + n * z);
+                    if old_tops[i] != tops[i] {
+                        darkened.extend(self.move_sky_top(
+                            world_coords([x, y, z, 0]),
+                            old_tops[i],
+                            tops[i],
+                            &mut sky_queue,
+                        ));
+                    }
+                }
+            }
+        }
+        sky_queue.extend(self.remove_light(darkened, Channel::Sky));
+
+        // Across each face of the chunk, light spreads from whichever side is brighter.
+        let own = &self.chunks[&which_chunk].light;
+        for axis in 0..4 {
+            for &(outside, inside) in &[(-1, 0), (size, size - 1)] {
+                let mut face_min = [0; 4];
+                let mut face_max = [size; 4];
+                face_min[axis] = outside;
+                face_max[axis] = outside + 1;
+                for x in face_min[0]..face_max[0] {
+                    for y in face_min[1]..face_max[1] {
+                        for z in face_min[2]..face_max[2] {
+                            for w in face_min[3]..face_max[3] {
+                                let outside_coords =
+                                    [corner[0] + x, corner[1] + y, corner[2] + z, corner[3] + w];
+                                let outside_light = match self.light(outside_coords) {
+                                    Some(light) => light,
+                                    None => continue,
+                                };
+                                let mut rel_pos = [x, y, z, w];
+                                rel_pos[axis] = inside;
+                                let inside_light = own.get(self.light_index(rel_pos));
+                                let mut inside_coords = outside_coords;
+                                inside_coords[axis] = corner[axis] + inside;
+
+                                for &channel in &Channel::ALL {
+                                    let queue = match channel {
+                                        Channel::Block => &mut block_queue,
+                                        Channel::Sky => &mut sky_queue,
+                                    };
+                                    let outside_level = channel.get(outside_light);
+                                    let inside_level = channel.get(inside_light);
+                                    if outside_level > inside_level + 1 {
+                                        queue.push_back(outside_coords);
+                                    } else if inside_level > outside_level + 1 {
+                                        queue.push_back(inside_coords);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        self.spread_light(block_queue, Channel::Block);
+        self.spread_light(sky_queue, Channel::Sky);
+
+        self.changes.light.remove(&which_chunk);
+    }
+}
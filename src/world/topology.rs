@@ -0,0 +1,69 @@
+/// The shape of the world along one axis. Sizes are in chunks.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Boundary {
+    Unbounded,
+    /// Only chunks `0..n` exist. Everything beyond them is made of barrier blocks.
+    Walled(usize),
+    /// Chunk `c` and chunk `c + n` are the same chunk.
+    Wrapping(usize),
+}
+
+/// The shape of the world along each axis.
+/// Wrapping along every axis makes the world a 4-torus.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Topology(pub [Boundary; 4]);
+
+impl Topology {
+    pub const UNBOUNDED: Self = Topology([Boundary::Unbounded; 4]);
+
+    /// The coordinates under which `which_chunk` is stored, or `None` if it lies beyond a wall.
+    pub fn canonical_chunk(&self, which_chunk: [isize; 4]) -> Option<[isize; 4]> {
+        let mut out = which_chunk;
+        for (c, boundary) in out.iter_mut().zip(self.0.iter()) {
+            match *boundary {
+                Boundary::Unbounded => {}
+                Boundary::Walled(n) => {
+                    if !(0..n as isize).contains(c) {
+                        return None;
+                    }
+                }
+                Boundary::Wrapping(n) => *c = c.rem_euclid(n as isize),
+            }
+        }
+        Some(out)
+    }
+}
+
+/// Parses four comma-separated boundaries, for the x, y, z and w axes, or a single boundary for all of them.
+/// A boundary is `unbounded`, `wall:n`, or `wrap:n`. For example, `wrap:2,wrap:2,wrap:2,wall:4`.
+impl std::str::FromStr for Topology {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        let boundaries = s
+            .split(',')
+            .map(|boundary| {
+                let mut parts = boundary.trim().splitn(2, ':');
+                let kind = parts.next().unwrap_or("");
+                let size = parts.next().map(|size| match size.parse::<usize>() {
+                    Ok(size) if size > 0 => Ok(size),
+                    _ => Err(format!("invalid boundary size {:?}", size)),
+                });
+                match (kind, size) {
+                    ("unbounded", None) => Ok(Boundary::Unbounded),
+                    ("wall", Some(size)) => Ok(Boundary::Walled(size?)),
+                    ("wrap", Some(size)) => Ok(Boundary::Wrapping(size?)),
+                    _ => Err(format!("invalid boundary {:?}", boundary)),
+                }
+            })
+            .collect::<Result<Vec<Boundary>, String>>()?;
+
+        match boundaries[..] {
+            [b] => Ok(Topology([b; 4])),
+            [x, y, z, w] => Ok(Topology([x, y, z, w])),
+            _ => Err(format!(
+                "expected 1 or 4 boundaries, found {}",
+                boundaries.len()
+            )),
+        }
+    }
+}